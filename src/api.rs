@@ -0,0 +1,468 @@
+use crate::{
+    governor::{Backoff, Endpoint, Governor},
+    models::{ErrorResponse, FundsResponse, GroupInfoResponse},
+    proxies::ProxyTarget,
+    GroupId,
+};
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde_json as json;
+use std::{
+    fmt,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Key appended to every request. Loaded from `api.key` before scanning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiKey(String);
+
+#[derive(Debug)]
+pub enum ApiKeyError {
+    Missing,
+    Unreadable(std::io::Error),
+    Empty,
+    /// The key contains whitespace or control characters.
+    Invalid,
+}
+
+impl fmt::Display for ApiKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiKeyError::Missing => write!(f, "the api key file does not exist"),
+            ApiKeyError::Unreadable(e) => write!(f, "the api key file could not be read: {}", e),
+            ApiKeyError::Empty => write!(f, "the api key is empty"),
+            ApiKeyError::Invalid => write!(f, "the api key contains spaces or control characters"),
+        }
+    }
+}
+
+impl std::error::Error for ApiKeyError {}
+
+impl ApiKey {
+    /// Validates a key, ignoring surrounding whitespace such as a trailing newline.
+    pub fn parse(key: &str) -> Result<Self, ApiKeyError> {
+        let key = key.trim();
+        if key.is_empty() {
+            Err(ApiKeyError::Empty)
+        } else if key.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            Err(ApiKeyError::Invalid)
+        } else {
+            Ok(ApiKey(key.to_string()))
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ApiKeyError> {
+        match std::fs::read_to_string(path) {
+            Ok(key) => Self::parse(&key),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(ApiKeyError::Missing),
+            Err(e) => Err(ApiKeyError::Unreadable(e)),
+        }
+    }
+
+    pub async fn save(&self, path: &Path) -> std::io::Result<()> {
+        tokio::fs::write(path, &self.0).await
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+pub const ECONOMY_URL: &str = "https://economy.roblox.com";
+pub const GROUPS_URL: &str = "https://groups.roblox.com";
+
+/// Base URLs of the services queried by the scanner.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Endpoints {
+    pub economy: String,
+    pub groups: String,
+}
+
+impl Endpoints {
+    /// Sends every request to one server, e.g. a local stub.
+    pub fn with_base_url(base_url: &str) -> Self {
+        let base_url = base_url.trim_end_matches('/').to_string();
+        Self {
+            economy: base_url.clone(),
+            groups: base_url,
+        }
+    }
+
+    fn funds_check_address(&self, id: GroupId, api_key: &str) -> String {
+        format!("{}/v1/groups/{}/currency?_={}", self.economy, id, api_key)
+    }
+
+    fn owner_check_address(&self, id: GroupId, api_key: &str) -> String {
+        format!("{}/v1/groups/{}?_={}", self.groups, id, api_key)
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            economy: ECONOMY_URL.to_string(),
+            groups: GROUPS_URL.to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    /// The request could not be sent or its response could not be read.
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+    /// Too many requests were made from this address. Carries how long to
+    /// wait, if the server or the connection's governor knows.
+    RateLimited { retry_after: Option<Duration> },
+    /// The group does not exist.
+    NotFound,
+    /// The server refused to answer this request.
+    Unauthorized,
+    /// Any other failure reported by the server.
+    Status(u16, Option<String>),
+    /// The response body could not be parsed.
+    Malformed(json::Error),
+}
+
+/// [`ApiError`] without its payload, for counting failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ApiErrorKind {
+    Transport,
+    RateLimited,
+    NotFound,
+    Unauthorized,
+    Status,
+    Malformed,
+}
+
+impl ApiError {
+    /// Classifies a response body and status code, or returns `None` if the
+    /// request succeeded.
+    pub fn from_response(status: u16, body: &str) -> Option<ApiError> {
+        let message = json::from_str::<ErrorResponse>(body)
+            .ok()
+            .and_then(|r| r.errors.into_iter().next())
+            .map(|e| e.message);
+        if status == 429 || message.as_deref() == Some("TooManyRequests") {
+            Some(ApiError::RateLimited { retry_after: None })
+        } else if status == 404 || message.as_deref().unwrap_or("").contains("does not exist") {
+            Some(ApiError::NotFound)
+        } else if status == 401 || status == 403 {
+            Some(ApiError::Unauthorized)
+        } else if !(200..300).contains(&status) || message.is_some() {
+            Some(ApiError::Status(status, message))
+        } else {
+            None
+        }
+    }
+
+    pub fn kind(&self) -> ApiErrorKind {
+        match self {
+            ApiError::Transport(_) => ApiErrorKind::Transport,
+            ApiError::RateLimited { .. } => ApiErrorKind::RateLimited,
+            ApiError::NotFound => ApiErrorKind::NotFound,
+            ApiError::Unauthorized => ApiErrorKind::Unauthorized,
+            ApiError::Status(..) => ApiErrorKind::Status,
+            ApiError::Malformed(_) => ApiErrorKind::Malformed,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApiError::Transport(e) => write!(f, "transport error: {}", e),
+            ApiError::RateLimited {
+                retry_after: Some(wait),
+            } => write!(f, "rate limited for {} seconds", wait.as_secs()),
+            ApiError::RateLimited { retry_after: None } => write!(f, "rate limited"),
+            ApiError::NotFound => write!(f, "group not found"),
+            ApiError::Unauthorized => write!(f, "unauthorized"),
+            ApiError::Status(status, Some(message)) => {
+                write!(f, "request failed with status {}: {}", status, message)
+            }
+            ApiError::Status(status, None) => write!(f, "request failed with status {}", status),
+            ApiError::Malformed(e) => write!(f, "malformed response body: {}", e),
+        }
+    }
+}
+
+impl fmt::Display for ApiErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ApiErrorKind::Transport => "transport",
+            ApiErrorKind::RateLimited => "rate limited",
+            ApiErrorKind::NotFound => "not found",
+            ApiErrorKind::Unauthorized => "unauthorized",
+            ApiErrorKind::Status => "other status",
+            ApiErrorKind::Malformed => "malformed",
+        })
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        ApiError::Transport(Box::new(e))
+    }
+}
+
+/// The requests the scanner makes about a single group.
+#[async_trait]
+pub trait GroupApi: Send + Sync {
+    async fn fetch_funds(&self, id: GroupId) -> Result<FundsResponse, ApiError>;
+    async fn fetch_group_info(&self, id: GroupId) -> Result<GroupInfoResponse, ApiError>;
+
+    /// How long the server took to answer the last request, not counting
+    /// time spent waiting for the rate limit.
+    fn last_latency(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Creates the [`GroupApi`] a worker uses for its proxy, or for a direct
+/// connection if it has none.
+pub trait Connector: Send + Sync {
+    fn connect(&self, proxy: Option<&ProxyTarget>) -> Result<Box<dyn GroupApi>, ApiError>;
+}
+
+pub struct ReqwestGroupApi {
+    client: reqwest::Client,
+    endpoints: Endpoints,
+    api_key: ApiKey,
+    governor: Governor,
+    last_latency: Mutex<Option<Duration>>,
+}
+
+impl ReqwestGroupApi {
+    pub fn new(
+        client: reqwest::Client,
+        endpoints: Endpoints,
+        api_key: ApiKey,
+        backoff: Backoff,
+    ) -> Self {
+        Self {
+            client,
+            endpoints,
+            api_key,
+            governor: Governor::new(backoff),
+            last_latency: Mutex::new(None),
+        }
+    }
+
+    async fn get_json<T: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        address: &str,
+    ) -> Result<T, ApiError> {
+        self.governor.acquire(endpoint).await;
+        let sent = Instant::now();
+        *self.last_latency.lock().unwrap() = None;
+        let res = self.client.get(address).send().await?;
+        let status = res.status().as_u16();
+        let headers = res.headers().clone();
+        let text = res.text().await?;
+        *self.last_latency.lock().unwrap() = Some(sent.elapsed());
+        let err = ApiError::from_response(status, &text);
+        let rate_limited = matches!(err, Some(ApiError::RateLimited { .. }));
+        let retry_after = self.governor.observe(endpoint, &headers, rate_limited);
+        match err {
+            Some(ApiError::RateLimited { .. }) => Err(ApiError::RateLimited { retry_after }),
+            Some(err) => Err(err),
+            None => json::from_str(&text).map_err(ApiError::Malformed),
+        }
+    }
+}
+
+#[async_trait]
+impl GroupApi for ReqwestGroupApi {
+    async fn fetch_funds(&self, id: GroupId) -> Result<FundsResponse, ApiError> {
+        self.get_json(
+            Endpoint::Economy,
+            &self
+                .endpoints
+                .funds_check_address(id, self.api_key.as_str()),
+        )
+        .await
+    }
+
+    async fn fetch_group_info(&self, id: GroupId) -> Result<GroupInfoResponse, ApiError> {
+        self.get_json(
+            Endpoint::Groups,
+            &self
+                .endpoints
+                .owner_check_address(id, self.api_key.as_str()),
+        )
+        .await
+    }
+
+    fn last_latency(&self) -> Option<Duration> {
+        *self.last_latency.lock().unwrap()
+    }
+}
+
+/// Connects through each proxy with `reqwest`. This is what the scanner uses
+/// unless told otherwise.
+#[derive(Debug, Clone)]
+pub struct ReqwestConnector {
+    pub endpoints: Endpoints,
+    pub api_key: ApiKey,
+    pub backoff: Backoff,
+}
+
+impl ReqwestConnector {
+    pub fn new(endpoints: Endpoints, api_key: ApiKey, backoff: Backoff) -> Self {
+        Self {
+            endpoints,
+            api_key,
+            backoff,
+        }
+    }
+}
+
+impl Connector for ReqwestConnector {
+    fn connect(&self, proxy: Option<&ProxyTarget>) -> Result<Box<dyn GroupApi>, ApiError> {
+        let mut client = reqwest::ClientBuilder::new();
+        if let Some(proxy) = proxy {
+            client = client.proxy(proxy.to_proxy()?);
+        }
+        let client = client.build()?;
+        Ok(Box::new(ReqwestGroupApi::new(
+            client,
+            self.endpoints.clone(),
+            self.api_key.clone(),
+            self.backoff,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_successful_responses() {
+        assert!(ApiError::from_response(200, r#"{"robux":5}"#).is_none());
+        assert!(ApiError::from_response(204, "").is_none());
+    }
+
+    #[test]
+    fn classifies_rate_limits() {
+        assert!(matches!(
+            ApiError::from_response(429, ""),
+            Some(ApiError::RateLimited { retry_after: None })
+        ));
+        let body = r#"{"errors":[{"code":0,"message":"TooManyRequests"}]}"#;
+        assert!(matches!(
+            ApiError::from_response(200, body),
+            Some(ApiError::RateLimited { .. })
+        ));
+    }
+
+    #[test]
+    fn classifies_missing_groups() {
+        assert!(matches!(
+            ApiError::from_response(404, ""),
+            Some(ApiError::NotFound)
+        ));
+        let body = r#"{"errors":[{"code":1,"message":"Group is invalid or does not exist."}]}"#;
+        assert!(matches!(
+            ApiError::from_response(400, body),
+            Some(ApiError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn classifies_refused_requests() {
+        assert!(matches!(
+            ApiError::from_response(401, ""),
+            Some(ApiError::Unauthorized)
+        ));
+        assert!(matches!(
+            ApiError::from_response(403, r#"{"errors":[{"message":"Forbidden"}]}"#),
+            Some(ApiError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn keeps_other_failures_with_their_message() {
+        assert!(matches!(
+            ApiError::from_response(503, "<html>Service Unavailable</html>"),
+            Some(ApiError::Status(503, None))
+        ));
+        match ApiError::from_response(200, r#"{"errors":[{"code":5,"message":"Oops"}]}"#) {
+            Some(ApiError::Status(200, Some(message))) => assert_eq!(message, "Oops"),
+            other => panic!("expected a status error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reads_funds() {
+        let funds: FundsResponse = json::from_str(r#"{"robux":120}"#).unwrap();
+        assert_eq!(funds.robux, 120);
+        assert!(json::from_str::<FundsResponse>(r#"{"robux":-1}"#).is_err());
+        assert!(json::from_str::<FundsResponse>("{}").is_err());
+    }
+
+    #[test]
+    fn reads_open_group_info() {
+        let body = r#"{
+            "id": 7,
+            "name": "Trading Hub",
+            "description": "Cheap limiteds",
+            "owner": null,
+            "shout": null,
+            "memberCount": 12,
+            "isBuildersClubOnly": false,
+            "publicEntryAllowed": true
+        }"#;
+        let info: GroupInfoResponse = json::from_str(body).unwrap();
+        assert_eq!(info.id, 7);
+        assert_eq!(info.name, "Trading Hub");
+        assert!(info.owner.is_none());
+        assert_eq!(info.member_count, Some(12));
+        assert!(info.public_entry_allowed);
+        assert_eq!(info.is_locked, None);
+        assert!(!info.locked());
+    }
+
+    #[test]
+    fn reads_owned_locked_group_info() {
+        let body = r#"{
+            "id": 8,
+            "name": "Closed",
+            "owner": {"userId": 2, "username": "bob", "displayName": "Bob"},
+            "shout": {"body": "hi", "poster": {"userId": 2, "username": "bob"}},
+            "isLocked": true
+        }"#;
+        let info: GroupInfoResponse = json::from_str(body).unwrap();
+        assert_eq!(info.owner.as_ref().map(|o| &o.username[..]), Some("bob"));
+        assert_eq!(info.shout.as_ref().map(|s| &s.body[..]), Some("hi"));
+        assert!(info.locked());
+        assert!(!info.public_entry_allowed);
+        assert_eq!(info.member_count, None);
+        assert_eq!(info.description, None);
+    }
+
+    #[test]
+    fn reads_group_info_without_an_owner_field() {
+        let info: GroupInfoResponse = json::from_str(r#"{"id": 9, "name": "Bare"}"#).unwrap();
+        assert!(info.owner.is_none());
+        assert!(!info.locked());
+    }
+
+    #[test]
+    fn sends_every_request_to_one_base_url() {
+        let endpoints = Endpoints::with_base_url("http://127.0.0.1:8080/");
+        assert_eq!(
+            endpoints.funds_check_address(5, "key"),
+            "http://127.0.0.1:8080/v1/groups/5/currency?_=key"
+        );
+        assert_eq!(
+            endpoints.owner_check_address(5, "key"),
+            "http://127.0.0.1:8080/v1/groups/5?_=key"
+        );
+    }
+}
@@ -0,0 +1,694 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use groupfinder::{
+    api::{ApiErrorKind, ApiKey},
+    config::{Config, ConfigError, Strategy, CONFIG_FILE},
+    connection::Connectedness,
+    proxies::{self, get_proxies_list, save_proxies_list, ProxyTarget},
+    results::{unix_time, Results},
+    scanner::{Event, ProxyStats, Scanner},
+    GroupId,
+};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+pub fn app() -> App<'static, 'static> {
+    App::new("groupfinder")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Finds ownerless groups that still have robux. Opens the GUI when no subcommand is given.")
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("FILE")
+                .global(true)
+                .help("Reads settings from this file instead of groupfinder.toml"),
+        )
+        .arg(
+            Arg::with_name("set")
+                .long("set")
+                .value_name("KEY=VALUE")
+                .multiple(true)
+                .number_of_values(1)
+                .global(true)
+                .help("Overrides a setting from the config file, e.g. --set wait_secs=20"),
+        )
+        .subcommand(
+            SubCommand::with_name("scan")
+                .about("Scans for groups from the command line, without the GUI")
+                .arg(
+                    Arg::with_name("min-robux")
+                        .long("min-robux")
+                        .value_name("ROBUX")
+                        .validator(|s| {
+                            s.parse::<u32>()
+                                .map(drop)
+                                .map_err(|e| format!("invalid robux amount: {}", e))
+                        })
+                        .help("Only report groups with at least this much robux"),
+                )
+                .arg(
+                    Arg::with_name("max-robux")
+                        .long("max-robux")
+                        .value_name("ROBUX")
+                        .validator(|s| {
+                            s.parse::<u32>()
+                                .map(drop)
+                                .map_err(|e| format!("invalid robux amount: {}", e))
+                        })
+                        .help("Only report groups with at most this much robux"),
+                )
+                .arg(
+                    Arg::with_name("max-rps")
+                        .long("max-rps")
+                        .value_name("REQUESTS")
+                        .validator(|s| match s.parse::<f64>() {
+                            Ok(rps) if rps >= 0. && rps.is_finite() => Ok(()),
+                            Ok(_) => Err("the request rate must be at least 0".to_string()),
+                            Err(e) => Err(format!("invalid request rate: {}", e)),
+                        })
+                        .help("Sends at most this many requests per second through all proxies together, 0 for no limit"),
+                )
+                .arg(
+                    Arg::with_name("direct")
+                        .long("direct")
+                        .value_name("WORKERS")
+                        .min_values(0)
+                        .max_values(1)
+                        .validator(|s| match s.parse::<u32>() {
+                            Ok(0) => Err("there must be at least 1 worker".to_string()),
+                            Ok(_) => Ok(()),
+                            Err(e) => Err(format!("invalid number of workers: {}", e)),
+                        })
+                        .help("Connects without a proxy, with 1 or WORKERS workers, at direct_requests_per_second unless --max-rps is given"),
+                )
+                .arg(
+                    Arg::with_name("premium")
+                        .long("premium")
+                        .help("Also report groups that only premium members can join"),
+                )
+                .arg(
+                    Arg::with_name("sweep")
+                        .long("sweep")
+                        .value_name("START..END")
+                        .validator(|s| parse_range(&s).map(drop))
+                        .help("Checks every group id from START up to END once, instead of random ids"),
+                )
+                .arg(
+                    Arg::with_name("stride")
+                        .long("stride")
+                        .value_name("N")
+                        .requires("sweep")
+                        .validator(|s| match s.parse::<u32>() {
+                            Ok(0) => Err("the stride must be greater than 0".to_string()),
+                            Ok(_) => Ok(()),
+                            Err(e) => Err(format!("invalid stride: {}", e)),
+                        })
+                        .help("Only checks every Nth group id of the sweep"),
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .conflicts_with_all(&["sweep", "stride"])
+                        .help("Continues the last unfinished scan, skipping the group ids it already checked"),
+                )
+                .arg(
+                    Arg::with_name("api-url")
+                        .long("api-url")
+                        .value_name("URL")
+                        .help("Send every API request to this base URL instead of the Roblox servers, e.g. a local stub server"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("results")
+                .about("Lists the groups found by previous scans")
+                .arg(
+                    Arg::with_name("unvisited")
+                        .long("unvisited")
+                        .help("Only list groups that have not been opened yet"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("recheck")
+                .about("Checks stored groups again and shows how their robux and owners changed")
+                .arg(
+                    Arg::with_name("ids")
+                        .value_name("GROUP_ID")
+                        .multiple(true)
+                        .validator(|s| {
+                            s.parse::<GroupId>()
+                                .map(drop)
+                                .map_err(|e| format!("invalid group id: {}", e))
+                        })
+                        .help("The groups to check, every stored group if none are given"),
+                )
+                .arg(
+                    Arg::with_name("direct")
+                        .long("direct")
+                        .help("Connects without a proxy, at direct_requests_per_second"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("test-proxies")
+                .about("Sends a request to proxy_test_url through every proxy and reports which ones answer")
+                .arg(
+                    Arg::with_name("remove-failed")
+                        .long("remove-failed")
+                        .help("Removes the proxies that failed from the proxies file"),
+                ),
+        )
+}
+
+/// Parses `START..END` into the start and end of a sweep.
+fn parse_range(s: &str) -> Result<(u32, u32), String> {
+    let mut parts = s.splitn(2, "..");
+    let (start, end) = match (parts.next(), parts.next()) {
+        (Some(start), Some(end)) => (start.trim(), end.trim()),
+        _ => return Err("expected START..END, e.g. 1..100000".to_string()),
+    };
+    let start = start
+        .parse::<u32>()
+        .map_err(|e| format!("invalid range start: {}", e))?;
+    let end = end
+        .parse::<u32>()
+        .map_err(|e| format!("invalid range end: {}", e))?;
+    if end <= start {
+        return Err("the range end must be greater than its start".to_string());
+    }
+    Ok((start, end))
+}
+
+fn format_coverage(coverage: Option<f64>) -> String {
+    match coverage {
+        Some(coverage) => format!(", {:.2}% of range covered", coverage),
+        None => String::new(),
+    }
+}
+
+fn format_request_errors(request_errors: &BTreeMap<ApiErrorKind, u32>) -> String {
+    if request_errors.is_empty() {
+        return String::new();
+    }
+    let counts = request_errors
+        .iter()
+        .map(|(kind, count)| format!("{} {}", count, kind))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(" (failed requests: {})", counts)
+}
+
+fn format_proxy_stats(stats: &ProxyStats) -> String {
+    format!(
+        "{} requests, {} succeeded, {} malformed, {} rate limited, average latency {}{}",
+        stats.requests,
+        stats.successes,
+        stats.parse_failures,
+        stats.rate_limited,
+        stats
+            .average_latency()
+            .map(|latency| format!("{}ms", latency.as_millis()))
+            .unwrap_or_else(|| "unknown".to_string()),
+        stats
+            .last_error
+            .as_ref()
+            .map(|err| format!(", last error: {}", err))
+            .unwrap_or_default()
+    )
+}
+
+/// Loads the config file named by `--config` (or `groupfinder.toml`), then
+/// applies environment variables and `--set` overrides.
+pub fn load_config(matches: &ArgMatches) -> Result<Config, ConfigError> {
+    let args = matches.subcommand().1.unwrap_or(matches);
+    let mut config = Config::load(args.value_of("config").map(Path::new))?;
+    for setting in args.values_of("set").into_iter().flatten() {
+        let mut parts = setting.splitn(2, '=');
+        let key = parts.next().unwrap().trim();
+        let value = parts.next().ok_or_else(|| ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: String::new(),
+            reason: "expected KEY=VALUE".to_string(),
+        })?;
+        config.set(key, value.trim())?;
+    }
+    config.validate()?;
+    Ok(config)
+}
+
+pub fn config_error(err: &ConfigError) {
+    eprintln!("Invalid configuration: {}", err);
+    if let ConfigError::UnknownKey(_) = err {
+        eprintln!(
+            "Valid keys for {} and --set are: {}",
+            CONFIG_FILE,
+            Config::KEYS.join(", ")
+        );
+    }
+}
+
+fn load_api_key(config: &Config) -> Option<ApiKey> {
+    match ApiKey::load(&config.api_key_file) {
+        Ok(api_key) => Some(api_key),
+        Err(err) => {
+            eprintln!(
+                "Could not load the api key from {}: {}",
+                config.api_key_file.display(),
+                err
+            );
+            eprintln!(
+                "Save your key in that file, or point api_key_file in {} at it.",
+                CONFIG_FILE
+            );
+            None
+        }
+    }
+}
+
+/// The proxies to connect through, none when connecting directly.
+async fn load_proxy_list(config: &Config) -> Option<Vec<ProxyTarget>> {
+    if config.direct_workers > 0 {
+        return Some(Vec::new());
+    }
+    let credentials_file = config.proxy_credentials_file.clone();
+    match get_proxies_list(config.proxies_file.clone(), credentials_file).await {
+        Ok(list) => {
+            for (line, reason) in &list.invalid {
+                eprintln!(
+                    "Skipping the proxy on line {} of {}: {}",
+                    line,
+                    config.proxies_file.display(),
+                    reason
+                );
+            }
+            Some(list.targets())
+        }
+        Err(err) => {
+            eprintln!(
+                "Could not load {} ({}). Generate one from the GUI first, or connect without proxies with --direct.",
+                config.proxies_file.display(),
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Runs a scan until every proxy disconnects or Ctrl-C is pressed.
+pub fn scan(mut config: Config, args: &ArgMatches) -> i32 {
+    if let Some(min) = args.value_of("min-robux") {
+        config.minimum_robux = min.parse().unwrap();
+    }
+    if let Some(max) = args.value_of("max-robux") {
+        config.maximum_robux = Some(max.parse().unwrap());
+    }
+    if let Some(rps) = args.value_of("max-rps") {
+        config.requests_per_second = rps.parse().unwrap();
+    }
+    if args.is_present("premium") {
+        config.premium_groups = true;
+    }
+    if args.is_present("direct") {
+        config.direct_workers = match args.value_of("direct") {
+            Some(workers) => workers.parse().unwrap(),
+            None => config.direct_workers.max(1),
+        };
+    }
+    if let Some(range) = args.value_of("sweep") {
+        let (start, end) = parse_range(range).unwrap();
+        config.strategy = Strategy::Sweep;
+        config.sweep_start = start;
+        config.sweep_end = end;
+    }
+    if let Some(stride) = args.value_of("stride") {
+        config.sweep_stride = stride.parse().unwrap();
+    }
+    if let Some(url) = args.value_of("api-url") {
+        config.api_url = Some(url.to_string());
+    }
+    // The flags can contradict the config file, e.g. --min-robux above maximum_robux
+    if let Err(err) = config.validate() {
+        config_error(&err);
+        return 2;
+    }
+    let api_key = match load_api_key(&config) {
+        Some(api_key) => api_key,
+        None => return 1,
+    };
+    let robux_range = config.robux_range();
+    let resume = args.is_present("resume");
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let proxy_list = match load_proxy_list(&config).await {
+            Some(proxy_list) => proxy_list,
+            None => return 1,
+        };
+        let results = match Results::open(&config.results_db) {
+            Ok(results) => results,
+            Err(err) => {
+                eprintln!("Could not open {}: {}", config.results_db.display(), err);
+                return 1;
+            }
+        };
+        let checkpoint = if resume {
+            match results.last_checkpoint().await {
+                Ok(Some(checkpoint)) => Some(checkpoint),
+                Ok(None) => {
+                    eprintln!("There is no unfinished scan to resume.");
+                    return 1;
+                }
+                Err(err) => {
+                    eprintln!("Could not read {}: {}", config.results_db.display(), err);
+                    return 1;
+                }
+            }
+        } else {
+            None
+        };
+        let connections = match config.direct_workers {
+            0 => format!("{} proxies", proxy_list.len()),
+            workers => format!("{} direct connections", workers),
+        };
+        let mut scanner = Scanner::new(config, proxy_list);
+        scanner.set_results(results);
+        scanner.set_api_key(api_key);
+        println!(
+            "Scanning with {} for groups with {}. Press Ctrl-C to stop.",
+            connections, robux_range
+        );
+        let rule = scanner.rule();
+        if !rule.to_string().is_empty() {
+            println!("Reporting groups that match: {}", rule);
+        }
+
+        let start_time = Instant::now();
+        let scan = match checkpoint {
+            Some(checkpoint) => {
+                println!(
+                    "Resuming session {}, which has checked {} groups since it started {}s ago",
+                    checkpoint.session,
+                    checkpoint.progress.groups_checked,
+                    unix_time() - checkpoint.started_at
+                );
+                scanner.resume(checkpoint)
+            }
+            None => scanner.start(),
+        }
+        .expect("api key and results were set");
+        if let Some(range) = scan.sweep_range() {
+            println!(
+                "Sweeping {} group ids from {} to {} with a stride of {}",
+                range.len(),
+                range.start,
+                range.end,
+                range.stride
+            );
+        }
+        if scan.rate_limit() > 0. {
+            println!("Sending at most {} requests per second", scan.rate_limit());
+        }
+        let mut events = scan.clone().events();
+        let mut progress = tokio::time::interval(PROGRESS_INTERVAL);
+        let mut request_errors = BTreeMap::new();
+        let mut proxy_stats = BTreeMap::new();
+        let mut proxy_states = BTreeMap::new();
+        let ctrl_c = tokio::signal::ctrl_c();
+        tokio::pin!(ctrl_c);
+        loop {
+            tokio::select! {
+                _ = &mut ctrl_c => {
+                    println!("Stopping scan, press Ctrl-C again to stop without waiting");
+                    scanner.stop();
+                    // Workers give back the groups they were checking before they end
+                    let drain = async { while events.recv().await.is_some() {} };
+                    tokio::select! {
+                        _ = drain => {}
+                        _ = tokio::signal::ctrl_c() => {}
+                    }
+                    break;
+                }
+                _ = progress.tick() => {
+                    let totals = scan.progress();
+                    println!(
+                        "[{}s] {} groups checked, {} found, {} robux total, {:.1} requests/s{}{}",
+                        start_time.elapsed().as_secs(),
+                        totals.groups_checked,
+                        totals.groups_found,
+                        totals.robux_found,
+                        scan.requests_sent() as f64 / start_time.elapsed().as_secs_f64().max(1.),
+                        format_coverage(scan.coverage()),
+                        format_request_errors(&request_errors)
+                    );
+                }
+                event = events.recv() => match event {
+                    Some(Event::GroupFound { info, robux }) => {
+                        println!(
+                            "Found group \"{}\" ({}) with {} robux: https://roblox.com/groups/{}",
+                            info.name,
+                            info.id,
+                            robux,
+                            info.id
+                        );
+                    }
+                    Some(Event::GroupChecked) => {}
+                    Some(Event::ProxyConnected(i, connectedness)) => {
+                        let previous = proxy_states.insert(i, connectedness);
+                        match connectedness {
+                            Connectedness::Active
+                                if previous != Some(Connectedness::CoolingDown) =>
+                            {
+                                println!("Proxy {} connected", i)
+                            }
+                            Connectedness::Backoff
+                                if matches!(previous, Some(state) if state.is_connected()) =>
+                            {
+                                println!("Proxy {} disconnected", i)
+                            }
+                            Connectedness::Retired => println!("Proxy {} retired", i),
+                            _ => {}
+                        }
+                    }
+                    Some(Event::RequestFailed(_, kind)) => {
+                        *request_errors.entry(kind).or_insert(0u32) += 1;
+                    }
+                    Some(Event::ProxyStats(i, stats)) => {
+                        proxy_stats.insert(i, stats);
+                    }
+                    None if matches!(scan.coverage(), Some(c) if c >= 100.) => {
+                        println!("Finished sweeping the range");
+                        break;
+                    }
+                    None => {
+                        println!("All proxies disconnected");
+                        break;
+                    }
+                }
+            }
+        }
+        scan.checkpoint().await;
+        let totals = scan.progress();
+        println!(
+            "Checked {} groups in {}s, found {} groups with {} robux total{}{}",
+            totals.groups_checked,
+            start_time.elapsed().as_secs(),
+            totals.groups_found,
+            totals.robux_found,
+            format_coverage(scan.coverage()),
+            format_request_errors(&request_errors)
+        );
+        for (i, stats) in &proxy_stats {
+            println!("Proxy {}: {}", i, format_proxy_stats(stats));
+        }
+        0
+    })
+}
+
+pub fn results(config: Config, args: &ArgMatches) -> i32 {
+    let unvisited = args.is_present("unvisited");
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let groups = match Results::open(&config.results_db) {
+            Ok(results) => results.groups().await,
+            Err(err) => Err(err),
+        };
+        let groups = match groups {
+            Ok(groups) => groups,
+            Err(err) => {
+                eprintln!("Could not read {}: {}", config.results_db.display(), err);
+                return 1;
+            }
+        };
+        for group in groups.iter().filter(|g| !unvisited || !g.visited) {
+            println!(
+                "{}\t{}\t{}{}{}\thttps://roblox.com/groups/{}",
+                group.id,
+                group.robux,
+                group.name.as_deref().unwrap_or("(unknown group name)"),
+                if group.premium { " (premium)" } else { "" },
+                group
+                    .disqualified()
+                    .map(|reason| format!(" (no longer qualifies: {})", reason))
+                    .unwrap_or_default(),
+                group.id
+            );
+        }
+        0
+    })
+}
+
+pub fn recheck(mut config: Config, args: &ArgMatches) -> i32 {
+    if args.is_present("direct") {
+        config.direct_workers = config.direct_workers.max(1);
+    }
+    let api_key = match load_api_key(&config) {
+        Some(api_key) => api_key,
+        None => return 1,
+    };
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let proxy_list = match load_proxy_list(&config).await {
+            Some(proxy_list) => proxy_list,
+            None => return 1,
+        };
+        let results = match Results::open(&config.results_db) {
+            Ok(results) => results,
+            Err(err) => {
+                eprintln!("Could not open {}: {}", config.results_db.display(), err);
+                return 1;
+            }
+        };
+        let groups = match results.groups().await {
+            Ok(groups) => groups,
+            Err(err) => {
+                eprintln!("Could not read {}: {}", config.results_db.display(), err);
+                return 1;
+            }
+        };
+        let ids = match args.values_of("ids") {
+            Some(ids) => ids.map(|id| id.parse().unwrap()).collect::<Vec<GroupId>>(),
+            None => groups.iter().map(|group| group.id).collect(),
+        };
+        if let Some(id) = ids.iter().find(|&&id| !groups.iter().any(|g| g.id == id)) {
+            eprintln!("Group {} is not in {}", id, config.results_db.display());
+            return 1;
+        }
+        println!("Checking {} groups again", ids.len());
+        let mut scanner = Scanner::new(config, proxy_list);
+        scanner.set_results(results);
+        scanner.set_api_key(api_key);
+        let outcomes = scanner.recheck(ids).unwrap().await;
+        let mut failed = 0;
+        let mut disqualified = 0;
+        for (id, outcome) in outcomes {
+            let before = groups.iter().find(|g| g.id == id).unwrap();
+            match outcome {
+                Ok(group) => {
+                    let snapshot = &group.snapshot;
+                    let status = match &snapshot.disqualified {
+                        Some(reason) => {
+                            disqualified += 1;
+                            format!("no longer qualifies: {}", reason)
+                        }
+                        None => "still qualifies".to_string(),
+                    };
+                    println!(
+                        "{}\t{} -> {} robux ({:+})\t{}\t{}",
+                        id,
+                        before.robux,
+                        snapshot.robux,
+                        snapshot.robux as i64 - before.robux as i64,
+                        group.info.name,
+                        status
+                    );
+                }
+                Err(reason) => {
+                    failed += 1;
+                    println!("{}\tfailed: {}", id, reason);
+                }
+            }
+        }
+        println!(
+            "{} no longer qualify, {} could not be checked",
+            disqualified, failed
+        );
+        0
+    })
+}
+
+pub fn test_proxies(config: Config, args: &ArgMatches) -> i32 {
+    let remove_failed = args.is_present("remove-failed");
+    let mut runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.block_on(async {
+        let credentials_file = config.proxy_credentials_file.clone();
+        let mut list = match get_proxies_list(config.proxies_file.clone(), credentials_file).await {
+            Ok(list) => list,
+            Err(err) => {
+                eprintln!("Could not load {} ({})", config.proxies_file.display(), err);
+                return 1;
+            }
+        };
+        let targets = list
+            .entries
+            .iter()
+            .filter(|entry| entry.invalid.is_none())
+            .map(|entry| ProxyTarget::new(entry, &list.credentials))
+            .collect::<Vec<_>>();
+        println!(
+            "Testing {} proxies against {}",
+            targets.len(),
+            config.proxy_test_url
+        );
+        let results = proxies::test_proxies(
+            targets.clone(),
+            config.proxy_test_url.clone(),
+            config.proxy_test_timeout(),
+        )
+        .await;
+        let mut failed = Vec::new();
+        for (target, result) in targets.iter().zip(results) {
+            let name = list
+                .entries
+                .iter()
+                .find(|entry| entry.url == target.url)
+                .map_or(&target.url[..], |entry| entry.name());
+            match result {
+                Ok(latency) => println!("ok\t{} ms\t{}", latency.as_millis(), name),
+                Err(reason) => {
+                    println!("failed\t{}\t{}", reason, name);
+                    failed.push(target.url.clone());
+                }
+            }
+        }
+        for (line, reason) in &list.invalid {
+            println!("invalid\tline {}: {}", line, reason);
+        }
+        println!(
+            "{} working, {} failed, {} invalid",
+            targets.len() - failed.len(),
+            failed.len(),
+            list.invalid.len()
+        );
+        if remove_failed && !failed.is_empty() {
+            list.entries.retain(|entry| !failed.contains(&entry.url));
+            if let Err(err) = save_proxies_list(
+                config.proxies_file.clone(),
+                config.proxy_credentials_file.clone(),
+                list.entries,
+                list.credentials,
+            )
+            .await
+            {
+                eprintln!("Could not save {}: {}", config.proxies_file.display(), err);
+                return 1;
+            }
+            println!(
+                "Removed {} proxies from {}",
+                failed.len(),
+                config.proxies_file.display()
+            );
+        }
+        0
+    })
+}
@@ -0,0 +1,476 @@
+use crate::{
+    api::Endpoints,
+    connection::Policy,
+    governor::Backoff,
+    ids::SweepRange,
+    logging::Filter,
+    rules::{RobuxRange, Rule, DEFAULT_RULE},
+    GroupId,
+};
+use serde::Deserialize;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const CONFIG_FILE: &str = "groupfinder.toml";
+/// Prefix of environment variables overriding config keys, e.g. `GROUPFINDER_WAIT_SECS`.
+pub const ENV_PREFIX: &str = "GROUPFINDER_";
+
+/// How a scan picks the group ids it checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Random ids below `max_group_id`, until the scan is stopped.
+    Random,
+    /// Every id from `sweep_start` to `sweep_end`, each checked once.
+    Sweep,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Strategy::Random => "random",
+            Strategy::Sweep => "sweep",
+        })
+    }
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Strategy::Random),
+            "sweep" => Ok(Strategy::Sweep),
+            _ => Err("expected `random` or `sweep`".to_string()),
+        }
+    }
+}
+
+/// Settings read from `groupfinder.toml`. Missing keys keep their defaults.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Longest a rate limited proxy waits before trying again, when the
+    /// server does not send `Retry-After`, and longest a proxy waits before
+    /// reconnecting.
+    pub cooldown_secs: u64,
+    /// First wait of a rate limited proxy, doubled each time it is rate
+    /// limited again, up to `cooldown_secs`. Also the first wait before a
+    /// proxy reconnects after a transport error, doubled for each failed
+    /// attempt in a row.
+    pub backoff_secs: u64,
+    /// How long each proxy waits between groups.
+    pub wait_secs: u64,
+    /// Most requests per second sent by all proxies together, or 0 for no limit.
+    pub requests_per_second: f64,
+    /// Workers that connect without a proxy. 0 to scan through the proxies
+    /// in `proxies_file` instead.
+    pub direct_workers: u32,
+    /// Request rate of direct workers while `requests_per_second` is 0, so
+    /// a scan without proxies does not get the user's own address blocked.
+    pub direct_requests_per_second: f64,
+    /// How often a running scan saves its progress so it can be resumed.
+    pub checkpoint_secs: u64,
+    /// Failed connections in a row before a proxy is given up on.
+    pub max_connect_attempts: u32,
+    /// Groups a proxy must have checked to keep reconnecting after
+    /// `max_connect_attempts` failures.
+    pub reconnect_threshold: u32,
+    pub strategy: Strategy,
+    /// Random scans pick group ids below this number.
+    pub max_group_id: GroupId,
+    /// First group id of a sweep.
+    pub sweep_start: GroupId,
+    /// A sweep stops before this group id.
+    pub sweep_end: GroupId,
+    /// Distance between the group ids of a sweep.
+    pub sweep_stride: GroupId,
+    pub minimum_robux: u32,
+    /// Groups with more robux than this are not reported. No limit when unset.
+    pub maximum_robux: Option<u32>,
+    pub premium_groups: bool,
+    /// Which groups with enough robux are reported, see [`Rule`].
+    pub group_rule: String,
+    pub results_db: PathBuf,
+    pub api_key_file: PathBuf,
+    pub proxies_file: PathBuf,
+    /// Usernames and passwords of the proxies in `proxies_file`, kept apart
+    /// so the proxies file can be shared.
+    pub proxy_credentials_file: PathBuf,
+    /// Requested through each proxy when the proxies are tested. Any server
+    /// works, e.g. a local one, since only getting an answer matters.
+    pub proxy_test_url: String,
+    /// How long a proxy has to answer the test request.
+    pub proxy_test_timeout_secs: u64,
+    /// Where log records are written. Empty to only log to the GUI and stderr.
+    pub log_file: PathBuf,
+    /// Which records are logged, e.g. `info` or `warn,groupfinder::scanner=debug`.
+    pub log_filter: String,
+    /// Size at which the log file is rotated.
+    pub log_max_kb: u64,
+    /// Sends every request to this server instead of the Roblox APIs, e.g. a local stub.
+    pub api_url: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            cooldown_secs: 60,
+            backoff_secs: 5,
+            wait_secs: 10,
+            requests_per_second: 0.,
+            direct_workers: 0,
+            direct_requests_per_second: 1.,
+            checkpoint_secs: 30,
+            max_connect_attempts: 5,
+            reconnect_threshold: 5,
+            strategy: Strategy::Random,
+            max_group_id: 5_000_000,
+            sweep_start: 1,
+            sweep_end: 5_000_000,
+            sweep_stride: 1,
+            minimum_robux: 1,
+            maximum_robux: None,
+            premium_groups: false,
+            group_rule: DEFAULT_RULE.to_string(),
+            results_db: PathBuf::from("groups.db"),
+            api_key_file: PathBuf::from("api.key"),
+            proxies_file: PathBuf::from("proxies.json"),
+            proxy_credentials_file: PathBuf::from("proxy_credentials.json"),
+            proxy_test_url: "https://groups.roblox.com/".to_string(),
+            proxy_test_timeout_secs: 10,
+            log_file: PathBuf::from("groupfinder.log"),
+            log_filter: "info".to_string(),
+            log_max_kb: 1024,
+            api_url: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+    UnknownKey(String),
+    InvalidValue {
+        key: String,
+        value: String,
+        reason: String,
+    },
+    Invalid {
+        key: &'static str,
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "could not parse {}: {}", path.display(), e),
+            ConfigError::UnknownKey(key) => write!(f, "unknown config key `{}`", key),
+            ConfigError::InvalidValue { key, value, reason } => {
+                write!(f, "invalid value `{}` for `{}`: {}", value, key, reason)
+            }
+            ConfigError::Invalid { key, reason } => write!(f, "`{}` {}", key, reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+fn parse_value<T>(key: &str, value: &str) -> Result<T, ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|e: T::Err| ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+            reason: e.to_string(),
+        })
+}
+
+impl Config {
+    pub const KEYS: &'static [&'static str] = &[
+        "cooldown_secs",
+        "backoff_secs",
+        "wait_secs",
+        "requests_per_second",
+        "direct_workers",
+        "direct_requests_per_second",
+        "checkpoint_secs",
+        "max_connect_attempts",
+        "reconnect_threshold",
+        "strategy",
+        "max_group_id",
+        "sweep_start",
+        "sweep_end",
+        "sweep_stride",
+        "minimum_robux",
+        "maximum_robux",
+        "premium_groups",
+        "group_rule",
+        "results_db",
+        "api_key_file",
+        "proxies_file",
+        "proxy_credentials_file",
+        "proxy_test_url",
+        "proxy_test_timeout_secs",
+        "log_file",
+        "log_filter",
+        "log_max_kb",
+        "api_url",
+    ];
+
+    /// Reads the config file, then applies `GROUPFINDER_*` environment
+    /// variables. Without an explicit path, a missing `groupfinder.toml`
+    /// just means the defaults are used.
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => (Path::new(CONFIG_FILE), false),
+        };
+        let mut config = match std::fs::read_to_string(path) {
+            Ok(s) => toml::from_str(&s).map_err(|e| ConfigError::Parse(path.to_owned(), e))?,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => Config::default(),
+            Err(e) => return Err(ConfigError::Read(path.to_owned(), e)),
+        };
+        for key in Self::KEYS {
+            let var = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            if let Ok(value) = std::env::var(&var) {
+                config.set(key, &value).map_err(|e| match e {
+                    ConfigError::InvalidValue { value, reason, .. } => ConfigError::InvalidValue {
+                        key: var.clone(),
+                        value,
+                        reason,
+                    },
+                    e => e,
+                })?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Overrides a single key, e.g. from the command line.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        match key {
+            "cooldown_secs" => self.cooldown_secs = parse_value(key, value)?,
+            "backoff_secs" => self.backoff_secs = parse_value(key, value)?,
+            "wait_secs" => self.wait_secs = parse_value(key, value)?,
+            "requests_per_second" => self.requests_per_second = parse_value(key, value)?,
+            "direct_workers" => self.direct_workers = parse_value(key, value)?,
+            "direct_requests_per_second" => {
+                self.direct_requests_per_second = parse_value(key, value)?
+            }
+            "checkpoint_secs" => self.checkpoint_secs = parse_value(key, value)?,
+            "max_connect_attempts" => self.max_connect_attempts = parse_value(key, value)?,
+            "reconnect_threshold" => self.reconnect_threshold = parse_value(key, value)?,
+            "strategy" => self.strategy = parse_value(key, value)?,
+            "max_group_id" => self.max_group_id = parse_value(key, value)?,
+            "sweep_start" => self.sweep_start = parse_value(key, value)?,
+            "sweep_end" => self.sweep_end = parse_value(key, value)?,
+            "sweep_stride" => self.sweep_stride = parse_value(key, value)?,
+            "minimum_robux" => self.minimum_robux = parse_value(key, value)?,
+            "maximum_robux" if value.is_empty() => self.maximum_robux = None,
+            "maximum_robux" => self.maximum_robux = Some(parse_value(key, value)?),
+            "premium_groups" => self.premium_groups = parse_value(key, value)?,
+            "group_rule" => {
+                parse_value::<Rule>(key, value)?;
+                self.group_rule = value.to_string();
+            }
+            "results_db" => self.results_db = PathBuf::from(value),
+            "api_key_file" => self.api_key_file = PathBuf::from(value),
+            "proxies_file" => self.proxies_file = PathBuf::from(value),
+            "proxy_credentials_file" => self.proxy_credentials_file = PathBuf::from(value),
+            "proxy_test_url" => self.proxy_test_url = value.to_string(),
+            "proxy_test_timeout_secs" => self.proxy_test_timeout_secs = parse_value(key, value)?,
+            "log_file" => self.log_file = PathBuf::from(value),
+            "log_filter" => {
+                parse_value::<Filter>(key, value)?;
+                self.log_filter = value.to_string();
+            }
+            "log_max_kb" => self.log_max_kb = parse_value(key, value)?,
+            "api_url" if value.is_empty() => self.api_url = None,
+            "api_url" => self.api_url = Some(value.to_string()),
+            _ => return Err(ConfigError::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.wait_secs == 0 {
+            return Err(ConfigError::Invalid {
+                key: "wait_secs",
+                reason: "must be at least 1 second",
+            });
+        }
+        if self.cooldown_secs == 0 {
+            return Err(ConfigError::Invalid {
+                key: "cooldown_secs",
+                reason: "must be at least 1 second",
+            });
+        }
+        if self.backoff_secs == 0 {
+            return Err(ConfigError::Invalid {
+                key: "backoff_secs",
+                reason: "must be at least 1 second",
+            });
+        }
+        if self.requests_per_second < 0. || !self.requests_per_second.is_finite() {
+            return Err(ConfigError::Invalid {
+                key: "requests_per_second",
+                reason: "must be a number of at least 0",
+            });
+        }
+        if self.direct_requests_per_second <= 0. || !self.direct_requests_per_second.is_finite() {
+            return Err(ConfigError::Invalid {
+                key: "direct_requests_per_second",
+                reason: "must be a number greater than 0",
+            });
+        }
+        if self.checkpoint_secs == 0 {
+            return Err(ConfigError::Invalid {
+                key: "checkpoint_secs",
+                reason: "must be at least 1 second",
+            });
+        }
+        if self.max_connect_attempts == 0 {
+            return Err(ConfigError::Invalid {
+                key: "max_connect_attempts",
+                reason: "must be at least 1",
+            });
+        }
+        if self.max_group_id == 0 {
+            return Err(ConfigError::Invalid {
+                key: "max_group_id",
+                reason: "must be greater than 0",
+            });
+        }
+        if self.sweep_stride == 0 {
+            return Err(ConfigError::Invalid {
+                key: "sweep_stride",
+                reason: "must be greater than 0",
+            });
+        }
+        if self.strategy == Strategy::Sweep && self.sweep_end <= self.sweep_start {
+            return Err(ConfigError::Invalid {
+                key: "sweep_end",
+                reason: "must be greater than sweep_start",
+            });
+        }
+        if RobuxRange::new(self.minimum_robux, self.maximum_robux).is_err() {
+            return Err(ConfigError::Invalid {
+                key: "maximum_robux",
+                reason: "must be at least minimum_robux",
+            });
+        }
+        if self.log_filter.parse::<Filter>().is_err() {
+            return Err(ConfigError::Invalid {
+                key: "log_filter",
+                reason:
+                    "must be a level like `info`, optionally followed by `,target=level` entries",
+            });
+        }
+        if let Err(e) = self.group_rule.parse::<Rule>() {
+            return Err(ConfigError::InvalidValue {
+                key: "group_rule".to_string(),
+                value: self.group_rule.clone(),
+                reason: e.to_string(),
+            });
+        }
+        if self.log_max_kb == 0 {
+            return Err(ConfigError::Invalid {
+                key: "log_max_kb",
+                reason: "must be greater than 0",
+            });
+        }
+        if reqwest::Url::parse(&self.proxy_test_url).is_err() {
+            return Err(ConfigError::Invalid {
+                key: "proxy_test_url",
+                reason: "must be a url like https://example.com/",
+            });
+        }
+        if self.proxy_test_timeout_secs == 0 {
+            return Err(ConfigError::Invalid {
+                key: "proxy_test_timeout_secs",
+                reason: "must be at least 1 second",
+            });
+        }
+        for (key, path) in &[
+            ("results_db", &self.results_db),
+            ("api_key_file", &self.api_key_file),
+            ("proxies_file", &self.proxies_file),
+            ("proxy_credentials_file", &self.proxy_credentials_file),
+        ] {
+            if path.as_os_str().is_empty() {
+                return Err(ConfigError::Invalid {
+                    key,
+                    reason: "must not be empty",
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn endpoints(&self) -> Endpoints {
+        match &self.api_url {
+            Some(url) => Endpoints::with_base_url(url),
+            None => Endpoints::default(),
+        }
+    }
+
+    pub fn robux_range(&self) -> RobuxRange {
+        RobuxRange {
+            minimum: self.minimum_robux,
+            maximum: self.maximum_robux,
+        }
+    }
+
+    pub fn sweep_range(&self) -> SweepRange {
+        SweepRange {
+            start: self.sweep_start,
+            end: self.sweep_end,
+            stride: self.sweep_stride,
+        }
+    }
+
+    pub fn cooldown_time(&self) -> Duration {
+        Duration::from_secs(self.cooldown_secs)
+    }
+
+    pub fn connection_policy(&self) -> Policy {
+        Policy {
+            max_attempts: self.max_connect_attempts,
+            reconnect_threshold: self.reconnect_threshold,
+        }
+    }
+
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            base: Duration::from_secs(self.backoff_secs),
+            max: self.cooldown_time(),
+        }
+    }
+
+    pub fn wait_time(&self) -> Duration {
+        Duration::from_secs(self.wait_secs)
+    }
+
+    pub fn group_rule(&self) -> Rule {
+        self.group_rule.parse().unwrap_or_default()
+    }
+
+    pub fn proxy_test_timeout(&self) -> Duration {
+        Duration::from_secs(self.proxy_test_timeout_secs)
+    }
+
+    pub fn checkpoint_time(&self) -> Duration {
+        Duration::from_secs(self.checkpoint_secs)
+    }
+}
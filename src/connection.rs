@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+/// Where a worker's connection through its proxy stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Connectedness {
+    /// Waiting for the first response through the proxy.
+    Connecting,
+    /// Checking groups.
+    Active,
+    /// Rate limited, waiting before the next request.
+    CoolingDown,
+    /// The connection failed, waiting before connecting again.
+    Backoff,
+    /// Gave up on the proxy, or the scan is over.
+    Retired,
+}
+
+impl Connectedness {
+    /// Requests are getting through the proxy.
+    pub fn is_connected(self) -> bool {
+        matches!(self, Connectedness::Active | Connectedness::CoolingDown)
+    }
+}
+
+/// What happened to a connection, moving it to its next state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// The proxy answered a request.
+    Connected,
+    /// A group was checked.
+    Checked,
+    /// The server rate limited a request and asked to wait this long.
+    RateLimited(Duration),
+    /// The cooldown is over.
+    CooledDown,
+    /// The proxy could not be reached or dropped the connection.
+    Failed,
+    /// The backoff is over.
+    BackedOff,
+    /// The scan was stopped or there are no group ids left.
+    Stopped,
+}
+
+/// When a connection gives up on its proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Policy {
+    /// Failed connections in a row before the proxy is retired.
+    pub max_attempts: u32,
+    /// Groups a proxy must have checked to keep reconnecting after
+    /// `max_attempts` failures.
+    pub reconnect_threshold: u32,
+}
+
+/// The state of one proxy's connection. Transitions only depend on the
+/// inputs, so the worker driving it decides how long to wait in each state.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    policy: Policy,
+    state: Connectedness,
+    /// Failed connections since the proxy last answered.
+    failures: u32,
+    groups_checked: u32,
+    cooldown: Duration,
+}
+
+impl Connection {
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            state: Connectedness::Connecting,
+            failures: 0,
+            groups_checked: 0,
+            cooldown: Duration::default(),
+        }
+    }
+
+    pub fn state(&self) -> Connectedness {
+        self.state
+    }
+
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// How long to wait while cooling down.
+    pub fn cooldown(&self) -> Duration {
+        self.cooldown
+    }
+
+    /// Moves to the state that follows `input` and returns it. Inputs that
+    /// do not apply to the current state are ignored.
+    pub fn handle(&mut self, input: Input) -> Connectedness {
+        use Connectedness::*;
+        self.state = match (self.state, input) {
+            (Retired, _) => Retired,
+            (_, Input::Stopped) => Retired,
+            (Connecting, Input::Connected) => {
+                self.failures = 0;
+                Active
+            }
+            (Active, Input::Checked) => {
+                self.groups_checked = self.groups_checked.saturating_add(1);
+                Active
+            }
+            (Connecting, Input::RateLimited(wait)) | (Active, Input::RateLimited(wait)) => {
+                self.cooldown = wait;
+                CoolingDown
+            }
+            (CoolingDown, Input::CooledDown) => Active,
+            (Connecting, Input::Failed)
+            | (Active, Input::Failed)
+            | (CoolingDown, Input::Failed) => {
+                self.failures += 1;
+                if self.failures < self.policy.max_attempts {
+                    Backoff
+                } else if self.groups_checked >= self.policy.reconnect_threshold {
+                    // The proxy has worked before, so it gets another round of attempts
+                    self.failures = 0;
+                    Backoff
+                } else {
+                    Retired
+                }
+            }
+            (Backoff, Input::BackedOff) => Connecting,
+            (state, _) => state,
+        };
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Connectedness::*;
+
+    const POLICY: Policy = Policy {
+        max_attempts: 3,
+        reconnect_threshold: 2,
+    };
+
+    fn connection_in(state: Connectedness) -> Connection {
+        let mut connection = Connection::new(POLICY);
+        let inputs: &[Input] = match state {
+            Connecting => &[],
+            Active => &[Input::Connected],
+            CoolingDown => &[Input::Connected, Input::RateLimited(Duration::from_secs(1))],
+            Backoff => &[Input::Failed],
+            Retired => &[Input::Stopped],
+        };
+        for &input in inputs {
+            connection.handle(input);
+        }
+        assert_eq!(connection.state(), state);
+        connection
+    }
+
+    #[test]
+    fn connects() {
+        let mut connection = Connection::new(POLICY);
+        assert_eq!(connection.state(), Connecting);
+        assert_eq!(connection.handle(Input::Connected), Active);
+        assert_eq!(connection.handle(Input::Checked), Active);
+    }
+
+    #[test]
+    fn cools_down_after_rate_limit() {
+        let mut connection = connection_in(Active);
+        let wait = Duration::from_secs(7);
+        assert_eq!(connection.handle(Input::RateLimited(wait)), CoolingDown);
+        assert_eq!(connection.cooldown(), wait);
+        // Groups are not checked while cooling down
+        assert_eq!(connection.handle(Input::Checked), CoolingDown);
+        assert_eq!(connection.handle(Input::CooledDown), Active);
+    }
+
+    #[test]
+    fn backs_off_after_failure() {
+        let mut connection = connection_in(Active);
+        assert_eq!(connection.handle(Input::Failed), Backoff);
+        assert_eq!(connection.failures(), 1);
+        assert_eq!(connection.handle(Input::BackedOff), Connecting);
+        assert_eq!(connection.handle(Input::Connected), Active);
+        assert_eq!(connection.failures(), 0);
+    }
+
+    #[test]
+    fn retires_at_max_attempts() {
+        let mut connection = Connection::new(POLICY);
+        for attempt in 1..POLICY.max_attempts {
+            assert_eq!(connection.handle(Input::Failed), Backoff);
+            assert_eq!(connection.failures(), attempt);
+            assert_eq!(connection.handle(Input::BackedOff), Connecting);
+        }
+        assert_eq!(connection.handle(Input::Failed), Retired);
+        assert_eq!(connection.handle(Input::BackedOff), Retired);
+        assert_eq!(connection.handle(Input::Connected), Retired);
+    }
+
+    #[test]
+    fn keeps_reconnecting_after_reconnect_threshold() {
+        let mut connection = connection_in(Active);
+        for _ in 0..POLICY.reconnect_threshold {
+            connection.handle(Input::Checked);
+        }
+        for _ in 0..POLICY.max_attempts - 1 {
+            assert_eq!(connection.handle(Input::Failed), Backoff);
+            connection.handle(Input::BackedOff);
+        }
+        // The proxy has checked enough groups to get another round of attempts
+        assert_eq!(connection.handle(Input::Failed), Backoff);
+        assert_eq!(connection.failures(), 0);
+        connection.handle(Input::BackedOff);
+        assert_eq!(connection.handle(Input::Failed), Backoff);
+        assert_eq!(connection.failures(), 1);
+    }
+
+    #[test]
+    fn retires_below_reconnect_threshold() {
+        let mut connection = connection_in(Active);
+        for _ in 0..POLICY.reconnect_threshold - 1 {
+            connection.handle(Input::Checked);
+        }
+        for _ in 0..POLICY.max_attempts - 1 {
+            assert_eq!(connection.handle(Input::Failed), Backoff);
+            connection.handle(Input::BackedOff);
+        }
+        assert_eq!(connection.handle(Input::Failed), Retired);
+    }
+
+    #[test]
+    fn stops_from_every_state() {
+        for &state in &[Connecting, Active, CoolingDown, Backoff, Retired] {
+            let mut connection = connection_in(state);
+            assert_eq!(
+                connection.handle(Input::Stopped),
+                Retired,
+                "from {:?}",
+                state
+            );
+        }
+    }
+}
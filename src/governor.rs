@@ -0,0 +1,406 @@
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{sync::watch, time::delay_for};
+
+const RATELIMIT_REMAINING: &str = "x-ratelimit-remaining";
+const RATELIMIT_RESET: &str = "x-ratelimit-reset";
+
+/// The services a [`Governor`] keeps separate budgets for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    Economy,
+    Groups,
+}
+
+/// How long to wait after being rate limited when the server does not say.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// Wait after the first rate limited response, doubled after each one that follows.
+    pub base: Duration,
+    /// Longest wait, however often the server rate limits.
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// Exponential backoff with jitter, between half and all of the full delay.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let full = self
+            .base
+            .checked_mul(1 << failures.saturating_sub(1).min(16))
+            .unwrap_or(self.max)
+            .min(self.max);
+        let jitter = rand::thread_rng().gen_range(0.5, 1.0);
+        full.mul_f64(jitter)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Budget {
+    /// No requests are sent before this.
+    blocked_until: Option<Instant>,
+    /// Requests left until `reset_at`, as last reported by the server.
+    remaining: Option<u32>,
+    reset_at: Option<Instant>,
+    last_request: Option<Instant>,
+    /// Rate limited responses in a row.
+    failures: u32,
+}
+
+impl Budget {
+    /// When the next request may be sent, spreading what is left of the
+    /// budget over the rest of its window.
+    fn next_request_at(&self, now: Instant) -> Instant {
+        let mut at = self.blocked_until.unwrap_or(now);
+        if let (Some(remaining), Some(reset_at)) = (self.remaining, self.reset_at) {
+            if reset_at > now {
+                let spaced = match (remaining, self.last_request) {
+                    (0, _) => reset_at,
+                    (_, Some(last)) => last + (reset_at - now) / remaining,
+                    (_, None) => now,
+                };
+                at = at.max(spaced);
+            }
+        }
+        at.max(now)
+    }
+}
+
+/// Paces the requests sent through one proxy, following the rate limits
+/// reported by the server.
+#[derive(Debug)]
+pub struct Governor {
+    backoff: Backoff,
+    economy: Mutex<Budget>,
+    groups: Mutex<Budget>,
+}
+
+fn header<T: std::str::FromStr>(
+    headers: &HeaderMap,
+    name: impl reqwest::header::AsHeaderName,
+) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+impl Governor {
+    pub fn new(backoff: Backoff) -> Self {
+        Self {
+            backoff,
+            economy: Default::default(),
+            groups: Default::default(),
+        }
+    }
+
+    fn budget(&self, endpoint: Endpoint) -> &Mutex<Budget> {
+        match endpoint {
+            Endpoint::Economy => &self.economy,
+            Endpoint::Groups => &self.groups,
+        }
+    }
+
+    /// Waits until a request to `endpoint` fits in its budget.
+    pub async fn acquire(&self, endpoint: Endpoint) {
+        let wait = {
+            let now = Instant::now();
+            let mut budget = self.budget(endpoint).lock().unwrap();
+            let at = budget.next_request_at(now);
+            budget.last_request = Some(at);
+            at - now
+        };
+        if wait > Duration::from_secs(0) {
+            delay_for(wait).await;
+        }
+    }
+
+    /// Updates the budget from a response's headers. Returns how long to
+    /// wait if the request was rate limited.
+    pub fn observe(
+        &self,
+        endpoint: Endpoint,
+        headers: &HeaderMap,
+        rate_limited: bool,
+    ) -> Option<Duration> {
+        let now = Instant::now();
+        let mut budget = self.budget(endpoint).lock().unwrap();
+        budget.remaining = header(headers, RATELIMIT_REMAINING);
+        budget.reset_at =
+            header(headers, RATELIMIT_RESET).map(|secs| now + Duration::from_secs(secs));
+        if !rate_limited {
+            budget.failures = 0;
+            return None;
+        }
+        budget.failures += 1;
+        let wait = match header(headers, RETRY_AFTER) {
+            Some(secs) => Duration::from_secs(secs),
+            None => self.backoff.delay(budget.failures),
+        };
+        budget.blocked_until = Some(now + wait);
+        Some(wait)
+    }
+}
+
+struct BucketState {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// Caps the requests per second of every worker together. The rate is read
+/// from a watch channel, so it can change while a scan runs. A rate of 0
+/// means `default_rate` is used instead, and a `default_rate` of 0 means
+/// no cap.
+pub struct TokenBucket {
+    rate: watch::Receiver<f64>,
+    default_rate: f64,
+    state: Mutex<BucketState>,
+    granted: AtomicU64,
+}
+
+impl TokenBucket {
+    pub fn new(rate: watch::Receiver<f64>, default_rate: f64) -> Self {
+        Self {
+            rate,
+            default_rate,
+            state: Mutex::new(BucketState {
+                tokens: 1.,
+                refilled: Instant::now(),
+            }),
+            granted: AtomicU64::new(0),
+        }
+    }
+
+    /// The current cap in requests per second, 0 if there is none.
+    pub fn rate(&self) -> f64 {
+        match *self.rate.borrow() {
+            rate if rate > 0. => rate,
+            _ => self.default_rate,
+        }
+    }
+
+    /// Waits until the cap allows another request.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let rate = self.rate();
+                let now = Instant::now();
+                let mut state = self.state.lock().unwrap();
+                let elapsed = (now - state.refilled).as_secs_f64();
+                state.refilled = now;
+                if rate <= 0. {
+                    state.tokens = 1.;
+                    None
+                } else {
+                    // Allows bursts of up to a second's worth of requests
+                    state.tokens = (state.tokens + elapsed * rate).min(rate.max(1.));
+                    if state.tokens >= 1. {
+                        state.tokens -= 1.;
+                        None
+                    } else {
+                        Some(Duration::from_secs_f64((1. - state.tokens) / rate))
+                    }
+                }
+            };
+            match wait {
+                Some(wait) => delay_for(wait).await,
+                None => break,
+            }
+        }
+        self.granted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Requests allowed so far.
+    pub fn granted(&self) -> u64 {
+        self.granted.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    const BACKOFF: Backoff = Backoff {
+        base: Duration::from_secs(1),
+        max: Duration::from_secs(10),
+    };
+
+    /// Checks many delays, as each is picked at random.
+    fn assert_delays_between(failures: u32, low_ms: u64, high_ms: u64) {
+        for _ in 0..200 {
+            let delay = BACKOFF.delay(failures);
+            assert!(
+                delay >= Duration::from_millis(low_ms) && delay <= Duration::from_millis(high_ms),
+                "{} failures waited {:?}",
+                failures,
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn doubles_the_backoff() {
+        assert_delays_between(0, 500, 1000);
+        assert_delays_between(1, 500, 1000);
+        assert_delays_between(2, 1000, 2000);
+        assert_delays_between(3, 2000, 4000);
+        assert_delays_between(4, 4000, 8000);
+    }
+
+    #[test]
+    fn caps_the_backoff() {
+        assert_delays_between(5, 5000, 10_000);
+        assert_delays_between(40, 5000, 10_000);
+        assert_delays_between(u32::MAX, 5000, 10_000);
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for &(name, value) in pairs {
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn failures(governor: &Governor, endpoint: Endpoint) -> u32 {
+        governor.budget(endpoint).lock().unwrap().failures
+    }
+
+    #[test]
+    fn honors_retry_after() {
+        let governor = Governor::new(BACKOFF);
+        let before = Instant::now();
+        let wait = governor.observe(Endpoint::Economy, &headers(&[("retry-after", "30")]), true);
+        assert_eq!(wait, Some(Duration::from_secs(30)));
+        let blocked_until = governor.economy.lock().unwrap().blocked_until.unwrap();
+        assert!(blocked_until >= before + Duration::from_secs(30));
+        // Each endpoint has its own budget
+        assert_eq!(failures(&governor, Endpoint::Economy), 1);
+        assert_eq!(failures(&governor, Endpoint::Groups), 0);
+        assert!(governor.groups.lock().unwrap().blocked_until.is_none());
+    }
+
+    #[test]
+    fn backs_off_until_a_request_succeeds() {
+        let governor = Governor::new(BACKOFF);
+        let none = HeaderMap::new();
+        let first = governor.observe(Endpoint::Groups, &none, true).unwrap();
+        assert!(first >= Duration::from_millis(500) && first <= Duration::from_secs(1));
+        let second = governor.observe(Endpoint::Groups, &none, true).unwrap();
+        assert!(second >= Duration::from_secs(1) && second <= Duration::from_secs(2));
+        assert_eq!(failures(&governor, Endpoint::Groups), 2);
+
+        assert_eq!(governor.observe(Endpoint::Groups, &none, false), None);
+        assert_eq!(failures(&governor, Endpoint::Groups), 0);
+        let again = governor.observe(Endpoint::Groups, &none, true).unwrap();
+        assert!(again <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn reads_the_budget_from_headers() {
+        let governor = Governor::new(BACKOFF);
+        let before = Instant::now();
+        let headers = headers(&[("x-ratelimit-remaining", "4"), ("x-ratelimit-reset", "8")]);
+        governor.observe(Endpoint::Economy, &headers, false);
+        let budget = governor.economy.lock().unwrap();
+        assert_eq!(budget.remaining, Some(4));
+        assert!(budget.reset_at.unwrap() >= before + Duration::from_secs(8));
+    }
+
+    #[test]
+    fn sends_right_away_without_a_budget() {
+        let now = Instant::now();
+        assert_eq!(Budget::default().next_request_at(now), now);
+        let budget = Budget {
+            blocked_until: Some(now - Duration::from_secs(1)),
+            ..Budget::default()
+        };
+        assert_eq!(budget.next_request_at(now), now);
+    }
+
+    #[test]
+    fn waits_while_blocked() {
+        let now = Instant::now();
+        let budget = Budget {
+            blocked_until: Some(now + Duration::from_secs(5)),
+            ..Budget::default()
+        };
+        assert_eq!(budget.next_request_at(now), now + Duration::from_secs(5));
+    }
+
+    #[test]
+    fn spreads_the_budget_over_its_window() {
+        let now = Instant::now();
+        let mut budget = Budget {
+            remaining: Some(4),
+            reset_at: Some(now + Duration::from_secs(8)),
+            last_request: Some(now),
+            ..Budget::default()
+        };
+        assert_eq!(budget.next_request_at(now), now + Duration::from_secs(2));
+        // The first request of a window goes out right away
+        budget.last_request = None;
+        assert_eq!(budget.next_request_at(now), now);
+        // A spent budget waits for the reset
+        budget.remaining = Some(0);
+        assert_eq!(budget.next_request_at(now), now + Duration::from_secs(8));
+        // A window that is over no longer counts
+        budget.reset_at = Some(now - Duration::from_secs(1));
+        assert_eq!(budget.next_request_at(now), now);
+    }
+
+    #[test]
+    fn takes_the_later_of_blocked_and_spread() {
+        let now = Instant::now();
+        let budget = Budget {
+            blocked_until: Some(now + Duration::from_secs(5)),
+            remaining: Some(0),
+            reset_at: Some(now + Duration::from_secs(8)),
+            ..Budget::default()
+        };
+        assert_eq!(budget.next_request_at(now), now + Duration::from_secs(8));
+    }
+
+    #[tokio::test]
+    async fn does_not_cap_a_rate_of_0() {
+        let (_sender, rate) = watch::channel(0.);
+        let bucket = TokenBucket::new(rate, 0.);
+        let start = Instant::now();
+        for _ in 0..1000 {
+            bucket.acquire().await;
+        }
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(bucket.granted(), 1000);
+        assert_eq!(bucket.rate(), 0.);
+    }
+
+    #[tokio::test]
+    async fn paces_requests_at_the_rate() {
+        let (_sender, rate) = watch::channel(20.);
+        let bucket = TokenBucket::new(rate, 0.);
+        let start = Instant::now();
+        // The first token is there from the start, the rest come every 50ms
+        for _ in 0..5 {
+            bucket.acquire().await;
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(190), "took {:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "took {:?}", elapsed);
+        assert_eq!(bucket.granted(), 5);
+    }
+
+    #[test]
+    fn falls_back_to_the_default_rate() {
+        let (sender, rate) = watch::channel(0.);
+        let bucket = TokenBucket::new(rate, 1.);
+        assert_eq!(bucket.rate(), 1.);
+        sender.broadcast(5.).unwrap();
+        assert_eq!(bucket.rate(), 5.);
+        sender.broadcast(0.).unwrap();
+        assert_eq!(bucket.rate(), 1.);
+    }
+}
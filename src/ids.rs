@@ -0,0 +1,463 @@
+use crate::{
+    config::{Config, Strategy},
+    GroupId,
+};
+use rand::random;
+use std::{collections::HashSet, sync::Mutex};
+
+/// Group ids from `start` up to, but not including, `end`, `stride` apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SweepRange {
+    pub start: GroupId,
+    pub end: GroupId,
+    pub stride: GroupId,
+}
+
+impl SweepRange {
+    /// Number of ids in the range.
+    pub fn len(&self) -> u64 {
+        if self.end <= self.start || self.stride == 0 {
+            0
+        } else {
+            (self.end - self.start - 1) as u64 / self.stride as u64 + 1
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, index: u64) -> Option<GroupId> {
+        if index < self.len() {
+            Some(self.start + (index * self.stride as u64) as GroupId)
+        } else {
+            None
+        }
+    }
+}
+
+/// State of a [`GroupIds`], saved in checkpoints so a scan can be resumed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IdsSnapshot {
+    Random {
+        max: GroupId,
+        /// Ids checked so far. [`GroupIds::snapshot`] only returns the ids
+        /// checked since its previous call.
+        checked: Vec<GroupId>,
+    },
+    Sweep {
+        range: SweepRange,
+        /// Index in the range of the next id to hand out.
+        next_index: u64,
+        checked: u64,
+        /// Ids that were handed out but not checked yet.
+        unfinished: Vec<GroupId>,
+    },
+}
+
+/// Hands out the ids a scan checks. Shared by all of the scan's workers.
+pub enum GroupIds {
+    /// Picks ids below `max` at random, skipping ids the scan already checked.
+    Random(Random),
+    /// Gives each id of the range to exactly one worker.
+    Sweep(Sweep),
+}
+
+pub struct Random {
+    max: GroupId,
+    checked: Mutex<HashSet<GroupId>>,
+    /// Checked ids not returned by a snapshot yet.
+    unsaved: Mutex<Vec<GroupId>>,
+}
+
+pub struct Sweep {
+    range: SweepRange,
+    /// Under one lock, so a snapshot never misses an id moving between its parts.
+    state: Mutex<SweepState>,
+}
+
+struct SweepState {
+    /// Index in the range of the next id to hand out.
+    next: u64,
+    checked: u64,
+    in_flight: HashSet<GroupId>,
+    /// Ids taken by workers that disconnected before checking them.
+    returned: Vec<GroupId>,
+}
+
+fn generate_random_group_id(max_group_id: GroupId) -> GroupId {
+    random::<GroupId>() % max_group_id
+}
+
+impl GroupIds {
+    pub fn new(config: &Config) -> Self {
+        match config.strategy {
+            Strategy::Random => GroupIds::restore(IdsSnapshot::Random {
+                max: config.max_group_id,
+                checked: Vec::new(),
+            }),
+            Strategy::Sweep => GroupIds::restore(IdsSnapshot::Sweep {
+                range: config.sweep_range(),
+                next_index: 0,
+                checked: 0,
+                unfinished: Vec::new(),
+            }),
+        }
+    }
+
+    /// Picks up where a snapshot left off. A random snapshot must list every
+    /// id checked so far.
+    pub fn restore(snapshot: IdsSnapshot) -> Self {
+        match snapshot {
+            IdsSnapshot::Random { max, checked } => GroupIds::Random(Random {
+                max,
+                checked: Mutex::new(checked.into_iter().collect()),
+                unsaved: Mutex::new(Vec::new()),
+            }),
+            IdsSnapshot::Sweep {
+                range,
+                next_index,
+                checked,
+                unfinished,
+            } => GroupIds::Sweep(Sweep {
+                range,
+                state: Mutex::new(SweepState {
+                    next: next_index,
+                    checked,
+                    in_flight: HashSet::new(),
+                    returned: unfinished,
+                }),
+            }),
+        }
+    }
+
+    pub fn snapshot(&self) -> IdsSnapshot {
+        match self {
+            GroupIds::Random(random) => IdsSnapshot::Random {
+                max: random.max,
+                checked: std::mem::take(&mut *random.unsaved.lock().unwrap()),
+            },
+            GroupIds::Sweep(sweep) => {
+                let state = sweep.state.lock().unwrap();
+                let mut unfinished = state.returned.clone();
+                unfinished.extend(state.in_flight.iter());
+                IdsSnapshot::Sweep {
+                    range: sweep.range,
+                    next_index: state.next.min(sweep.range.len()),
+                    checked: state.checked,
+                    unfinished,
+                }
+            }
+        }
+    }
+
+    /// Takes back a snapshot that could not be saved, so the next snapshot
+    /// of a random scan still lists the ids it held.
+    pub fn unsaved(&self, snapshot: IdsSnapshot) {
+        if let (GroupIds::Random(random), IdsSnapshot::Random { checked, .. }) = (self, snapshot) {
+            random.unsaved.lock().unwrap().extend(checked);
+        }
+    }
+
+    /// The next id to check, or `None` once every id has been handed out.
+    pub fn next(&self) -> Option<GroupId> {
+        match self {
+            GroupIds::Random(random) => {
+                let checked = random.checked.lock().unwrap();
+                if checked.len() as u64 >= random.max as u64 {
+                    return None;
+                }
+                loop {
+                    let id = generate_random_group_id(random.max);
+                    if !checked.contains(&id) {
+                        return Some(id);
+                    }
+                }
+            }
+            GroupIds::Sweep(sweep) => {
+                let mut state = sweep.state.lock().unwrap();
+                let id = match state.returned.pop() {
+                    Some(id) => id,
+                    None => {
+                        let id = sweep.range.get(state.next)?;
+                        state.next += 1;
+                        id
+                    }
+                };
+                state.in_flight.insert(id);
+                Some(id)
+            }
+        }
+    }
+
+    /// Returns an id that was taken but not checked, so another worker checks it.
+    pub fn give_back(&self, id: GroupId) {
+        if let GroupIds::Sweep(sweep) = self {
+            let mut state = sweep.state.lock().unwrap();
+            state.in_flight.remove(&id);
+            state.returned.push(id);
+        }
+    }
+
+    pub fn mark_checked(&self, id: GroupId) {
+        match self {
+            GroupIds::Random(random) => {
+                if random.checked.lock().unwrap().insert(id) {
+                    random.unsaved.lock().unwrap().push(id);
+                }
+            }
+            GroupIds::Sweep(sweep) => {
+                let mut state = sweep.state.lock().unwrap();
+                if state.in_flight.remove(&id) {
+                    state.checked += 1;
+                }
+            }
+        }
+    }
+
+    /// Every id has been checked, so there is nothing left to resume.
+    pub fn is_finished(&self) -> bool {
+        match self {
+            GroupIds::Random(random) => {
+                random.checked.lock().unwrap().len() as u64 >= random.max as u64
+            }
+            GroupIds::Sweep(sweep) => sweep.state.lock().unwrap().checked >= sweep.range.len(),
+        }
+    }
+
+    pub fn sweep_range(&self) -> Option<SweepRange> {
+        match self {
+            GroupIds::Random(_) => None,
+            GroupIds::Sweep(sweep) => Some(sweep.range),
+        }
+    }
+
+    /// Percentage of the sweep range checked so far. Always `None` for random scans.
+    pub fn coverage(&self) -> Option<f64> {
+        match self {
+            GroupIds::Random(_) => None,
+            GroupIds::Sweep(sweep) => {
+                let len = sweep.range.len();
+                let checked = sweep.state.lock().unwrap().checked;
+                Some(if len == 0 {
+                    100.
+                } else {
+                    checked as f64 / len as f64 * 100.
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: GroupId, end: GroupId, stride: GroupId) -> SweepRange {
+        SweepRange { start, end, stride }
+    }
+
+    fn ids(range: SweepRange) -> Vec<GroupId> {
+        (0..range.len()).map(|i| range.get(i).unwrap()).collect()
+    }
+
+    #[test]
+    fn stops_before_the_end() {
+        assert_eq!(range(1, 6, 1).len(), 5);
+        assert_eq!(ids(range(1, 6, 1)), vec![1, 2, 3, 4, 5]);
+        assert_eq!(range(1, 6, 1).get(5), None);
+    }
+
+    #[test]
+    fn strides_over_the_range() {
+        assert_eq!(ids(range(1, 10, 3)), vec![1, 4, 7]);
+        assert_eq!(ids(range(1, 11, 3)), vec![1, 4, 7, 10]);
+        assert_eq!(ids(range(5, 6, 100)), vec![5]);
+    }
+
+    #[test]
+    fn holds_a_single_id() {
+        let single = range(7, 8, 1);
+        assert_eq!(single.len(), 1);
+        assert!(!single.is_empty());
+        assert_eq!(single.get(0), Some(7));
+        assert_eq!(single.get(1), None);
+    }
+
+    #[test]
+    fn is_empty_when_it_ends_at_its_start() {
+        assert!(range(7, 7, 1).is_empty());
+        assert!(range(8, 7, 1).is_empty());
+        assert!(range(1, 100, 0).is_empty());
+        assert_eq!(range(7, 7, 1).get(0), None);
+    }
+
+    #[test]
+    fn reaches_the_largest_ids() {
+        let max = GroupId::MAX;
+        assert_eq!(ids(range(max - 3, max, 1)), vec![max - 3, max - 2, max - 1]);
+        let whole = range(0, max, 1);
+        assert_eq!(whole.len(), max as u64);
+        assert_eq!(whole.get(max as u64 - 1), Some(max - 1));
+        assert_eq!(whole.get(max as u64), None);
+        let wide = range(0, max, max / 2);
+        assert_eq!(ids(wide), vec![0, max / 2, max / 2 * 2]);
+    }
+
+    fn drain(ids: &GroupIds) -> Vec<GroupId> {
+        let mut all: Vec<_> = std::iter::from_fn(|| ids.next()).collect();
+        all.sort_unstable();
+        all
+    }
+
+    #[test]
+    fn resumed_sweep_skips_checked_ids() {
+        let ids = GroupIds::restore(IdsSnapshot::Sweep {
+            range: range(1, 11, 1),
+            next_index: 0,
+            checked: 0,
+            unfinished: Vec::new(),
+        });
+        let taken: Vec<_> = (0..4).map(|_| ids.next().unwrap()).collect();
+        assert_eq!(taken, vec![1, 2, 3, 4]);
+        ids.mark_checked(1);
+        ids.mark_checked(2);
+        // 3 was handed back by a worker that disconnected, 4 is still in flight
+        ids.give_back(3);
+
+        let snapshot = ids.snapshot();
+        match &snapshot {
+            IdsSnapshot::Sweep {
+                next_index,
+                checked,
+                unfinished,
+                ..
+            } => {
+                assert_eq!(*next_index, 4);
+                assert_eq!(*checked, 2);
+                let mut unfinished = unfinished.clone();
+                unfinished.sort_unstable();
+                assert_eq!(unfinished, vec![3, 4]);
+            }
+            IdsSnapshot::Random { .. } => panic!("expected a sweep snapshot"),
+        }
+
+        let resumed = GroupIds::restore(snapshot);
+        let rest = drain(&resumed);
+        assert_eq!(rest, vec![3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(resumed.coverage(), Some(20.));
+        assert!(!resumed.is_finished());
+        for id in rest {
+            resumed.mark_checked(id);
+        }
+        assert!(resumed.is_finished());
+        assert_eq!(resumed.coverage(), Some(100.));
+    }
+
+    #[test]
+    fn checking_an_id_twice_counts_once() {
+        let ids = GroupIds::restore(IdsSnapshot::Sweep {
+            range: range(1, 3, 1),
+            next_index: 0,
+            checked: 0,
+            unfinished: Vec::new(),
+        });
+        let id = ids.next().unwrap();
+        ids.mark_checked(id);
+        ids.mark_checked(id);
+        assert_eq!(ids.coverage(), Some(50.));
+    }
+
+    #[test]
+    fn resumed_random_scan_skips_checked_ids() {
+        let ids = GroupIds::restore(IdsSnapshot::Random {
+            max: 4,
+            checked: Vec::new(),
+        });
+        for id in 0..3 {
+            ids.mark_checked(id);
+        }
+        let checked = match ids.snapshot() {
+            IdsSnapshot::Random { checked, .. } => checked,
+            IdsSnapshot::Sweep { .. } => panic!("expected a random snapshot"),
+        };
+        assert_eq!(checked, vec![0, 1, 2]);
+        // Later snapshots only hold what was checked since
+        assert_eq!(
+            ids.snapshot(),
+            IdsSnapshot::Random {
+                max: 4,
+                checked: Vec::new()
+            }
+        );
+
+        let resumed = GroupIds::restore(IdsSnapshot::Random { max: 4, checked });
+        for _ in 0..20 {
+            assert_eq!(resumed.next(), Some(3));
+        }
+        resumed.mark_checked(3);
+        assert_eq!(resumed.next(), None);
+        assert!(resumed.is_finished());
+    }
+
+    #[test]
+    fn snapshots_account_for_every_id_handed_out() {
+        let ids = std::sync::Arc::new(GroupIds::restore(IdsSnapshot::Sweep {
+            range: range(0, 20_000, 1),
+            next_index: 0,
+            checked: 0,
+            unfinished: Vec::new(),
+        }));
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let ids = ids.clone();
+                std::thread::spawn(move || {
+                    while let Some(id) = ids.next() {
+                        if id % 3 == 0 {
+                            ids.give_back(id);
+                            if let Some(id) = ids.next() {
+                                ids.mark_checked(id);
+                            }
+                        } else {
+                            ids.mark_checked(id);
+                        }
+                    }
+                })
+            })
+            .collect();
+        while !ids.is_finished() {
+            match ids.snapshot() {
+                IdsSnapshot::Sweep {
+                    next_index,
+                    checked,
+                    unfinished,
+                    ..
+                } => assert_eq!(checked + unfinished.len() as u64, next_index),
+                IdsSnapshot::Random { .. } => unreachable!(),
+            }
+        }
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn keeps_ids_of_snapshots_that_were_not_saved() {
+        let ids = GroupIds::restore(IdsSnapshot::Random {
+            max: 10,
+            checked: Vec::new(),
+        });
+        ids.mark_checked(1);
+        ids.mark_checked(2);
+        let failed = ids.snapshot();
+        ids.mark_checked(3);
+        ids.unsaved(failed);
+        match ids.snapshot() {
+            IdsSnapshot::Random { mut checked, .. } => {
+                checked.sort_unstable();
+                assert_eq!(checked, vec![1, 2, 3]);
+            }
+            IdsSnapshot::Sweep { .. } => panic!("expected a random snapshot"),
+        }
+    }
+}
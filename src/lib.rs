@@ -0,0 +1,16 @@
+#[macro_use]
+extern crate lazy_static;
+
+pub mod api;
+pub mod config;
+pub mod connection;
+pub mod governor;
+pub mod ids;
+pub mod logging;
+pub mod models;
+pub mod proxies;
+pub mod results;
+pub mod rules;
+pub mod scanner;
+
+pub type GroupId = u32;
@@ -0,0 +1,236 @@
+use crate::config::Config;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Records kept in memory for the log pane.
+pub const HISTORY_LEN: usize = 500;
+/// Rotated log files kept next to the current one, e.g. `groupfinder.log.1`.
+const ROTATED_FILES: u32 = 3;
+
+lazy_static! {
+    static ref HISTORY: Mutex<VecDeque<Entry>> = Mutex::new(VecDeque::with_capacity(HISTORY_LEN));
+    static ref SUBSCRIBER: Mutex<Option<UnboundedSender<Entry>>> = Mutex::new(None);
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Local time, e.g. `2020-08-01 12:00:00`.
+    pub time: String,
+    pub level: Level,
+    pub target: String,
+    /// Starts with `key=value` fields, e.g. `event=rate_limited proxy=3`,
+    /// so the log can be searched for one proxy or group.
+    pub message: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {:<5} {} {}",
+            self.time, self.level, self.target, self.message
+        )
+    }
+}
+
+/// Which records are logged, e.g. `info` or `warn,groupfinder::scanner=debug`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    default: LevelFilter,
+    /// Levels of targets starting with the given prefix.
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = Filter {
+            default: LevelFilter::Info,
+            targets: Vec::new(),
+        };
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let mut parts = directive.splitn(2, '=');
+            let (target, level) = match (parts.next(), parts.next()) {
+                (Some(level), None) => (None, level),
+                (Some(target), Some(level)) => (Some(target.trim()), level),
+                _ => unreachable!(),
+            };
+            let level = level
+                .trim()
+                .parse::<LevelFilter>()
+                .map_err(|_| format!("unknown log level `{}`", level.trim()))?;
+            match target {
+                Some(target) => filter.targets.push((target.to_string(), level)),
+                None => filter.default = level,
+            }
+        }
+        // Longest prefixes first, so the most specific directive wins
+        filter
+            .targets
+            .sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+        Ok(filter)
+    }
+}
+
+impl Filter {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| target.starts_with(prefix.as_str()))
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+/// Appends to `path`, moving it to `path.1` once it grows past `max_bytes`.
+struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    file: File,
+    size: u64,
+}
+
+fn rotated_path(path: &Path, n: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+impl RotatingFile {
+    fn open(path: &Path, max_bytes: u64) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path: path.to_owned(),
+            max_bytes,
+            file,
+            size,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..ROTATED_FILES).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        *self = Self::open(&self.path, self.max_bytes)?;
+        Ok(())
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size > 0 && self.size + line.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        writeln!(self.file, "{}", line)?;
+        self.size += line.len() as u64 + 1;
+        Ok(())
+    }
+}
+
+struct Logger {
+    filter: Filter,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let entry = Entry {
+            time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        };
+        let line = entry.to_string();
+        if entry.level <= Level::Warn {
+            eprintln!("{}", line);
+        }
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            if let Err(e) = file.write_line(&line) {
+                eprintln!("Could not write to the log file: {}", e);
+            }
+        }
+        {
+            let mut history = HISTORY.lock().unwrap();
+            if history.len() == HISTORY_LEN {
+                history.pop_front();
+            }
+            history.push_back(entry.clone());
+        }
+        let mut subscriber = SUBSCRIBER.lock().unwrap();
+        if let Some(tx) = subscriber.as_ref() {
+            if tx.send(entry).is_err() {
+                *subscriber = None;
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap().as_mut() {
+            file.file.flush().ok();
+        }
+    }
+}
+
+/// Installs the logger described by the config. Records go to the rotating
+/// log file and the log pane, and warnings and errors also to stderr. If the
+/// log file cannot be opened, the other two still work.
+pub fn init(config: &Config) -> io::Result<()> {
+    let filter = config
+        .log_filter
+        .parse::<Filter>()
+        .unwrap_or_else(|_| "info".parse().unwrap());
+    let (file, result) = if config.log_file.as_os_str().is_empty() {
+        (None, Ok(()))
+    } else {
+        match RotatingFile::open(&config.log_file, config.log_max_kb * 1024) {
+            Ok(file) => (Some(file), Ok(())),
+            Err(e) => (None, Err(e)),
+        }
+    };
+    log::set_max_level(filter.max_level());
+    let logger = Box::new(Logger {
+        filter,
+        file: Mutex::new(file),
+    });
+    // Only fails if a logger was already installed
+    log::set_logger(Box::leak(logger)).ok();
+    result
+}
+
+/// The most recent records, oldest first.
+pub fn history() -> Vec<Entry> {
+    HISTORY.lock().unwrap().iter().cloned().collect()
+}
+
+/// Receives every record logged from now on. Replaces the previous subscriber.
+pub fn subscribe() -> UnboundedReceiver<Entry> {
+    let (tx, rx) = unbounded_channel();
+    *SUBSCRIBER.lock().unwrap() = Some(tx);
+    rx
+}
@@ -1,19 +1,40 @@
-#[macro_use]
-extern crate lazy_static;
-
+mod cli;
+mod proxy_editor;
 mod scraping;
 mod ui;
 
 use iced::Application;
 
-pub type GroupId = u32;
-
 fn main() {
+    let matches = cli::app().get_matches();
+    let config = match cli::load_config(&matches) {
+        Ok(config) => config,
+        Err(err) => {
+            cli::config_error(&err);
+            std::process::exit(2);
+        }
+    };
+    if let Err(err) = groupfinder::logging::init(&config) {
+        eprintln!(
+            "Could not open the log file {}: {}",
+            config.log_file.display(),
+            err
+        );
+    }
+    match matches.subcommand() {
+        ("scan", Some(args)) => std::process::exit(cli::scan(config, args)),
+        ("results", Some(args)) => std::process::exit(cli::results(config, args)),
+        ("recheck", Some(args)) => std::process::exit(cli::recheck(config, args)),
+        ("test-proxies", Some(args)) => std::process::exit(cli::test_proxies(config, args)),
+        _ => {}
+    }
+
     let settings = iced::Settings {
         window: iced::window::Settings {
             size: (1000, 600),
             ..Default::default()
         },
+        flags: config,
         ..Default::default()
     };
     ui::GroupScraper::run(settings)
@@ -0,0 +1,73 @@
+use crate::GroupId;
+use serde::{Deserialize, Serialize};
+
+/// `GET economy.roblox.com/v1/groups/{id}/currency`
+#[derive(Debug, Clone, Deserialize)]
+pub struct FundsResponse {
+    pub robux: u32,
+}
+
+/// `GET groups.roblox.com/v1/groups/{id}`
+///
+/// Also stored as JSON in the results database, in the same shape.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupInfoResponse {
+    pub id: GroupId,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub owner: Option<GroupOwner>,
+    #[serde(default)]
+    pub shout: Option<GroupShout>,
+    #[serde(default)]
+    pub member_count: Option<u64>,
+    #[serde(default)]
+    pub is_builders_club_only: bool,
+    #[serde(default)]
+    pub public_entry_allowed: bool,
+    /// Only sent for locked groups.
+    #[serde(default)]
+    pub is_locked: Option<bool>,
+}
+
+impl GroupInfoResponse {
+    pub fn locked(&self) -> bool {
+        self.is_locked.unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupOwner {
+    pub user_id: u64,
+    pub username: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupShout {
+    pub body: String,
+    #[serde(default)]
+    pub poster: Option<GroupOwner>,
+    #[serde(default)]
+    pub created: Option<String>,
+    #[serde(default)]
+    pub updated: Option<String>,
+}
+
+/// Body sent alongside a failed request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ErrorResponse {
+    pub errors: Vec<ErrorEntry>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ErrorEntry {
+    #[serde(default)]
+    pub code: i64,
+    #[serde(default)]
+    pub message: String,
+}
@@ -0,0 +1,428 @@
+use crate::ui::Msg;
+use groupfinder::{
+    config::Config,
+    proxies::{
+        import_proxies_text, save_proxies_list, test_proxies, CredentialStore, Credentials,
+        ProxyEntry, ProxyList, ProxyTarget,
+    },
+};
+use iced::{widget, Color, Command, Element, Length};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+#[derive(Debug, Clone)]
+pub enum EditorMsg {
+    UrlChanged(String),
+    UsernameChanged(String),
+    PasswordChanged(String),
+    Add,
+    Remove(usize),
+    SetEnabled(usize, bool),
+    MoveUp(usize),
+    MoveDown(usize),
+    ImportPathChanged(String),
+    Import,
+    Imported(Result<(Vec<ProxyEntry>, Vec<(usize, String)>), String>),
+    Save,
+    Saved(Result<(), String>),
+    Test,
+    Tested(Vec<(String, Result<Duration, String>)>),
+    RemoveFailed,
+}
+
+#[derive(Default)]
+struct RowState {
+    up: widget::button::State,
+    down: widget::button::State,
+    remove: widget::button::State,
+}
+
+/// Edits a copy of the proxy list, which replaces the proxies file and the
+/// credentials file when saved.
+pub struct ProxyEditor {
+    path: PathBuf,
+    credentials_path: PathBuf,
+    entries: Vec<ProxyEntry>,
+    credentials: CredentialStore,
+    test_url: String,
+    test_timeout: Duration,
+    /// Latency or error of each url in the last test.
+    test_results: HashMap<String, Result<Duration, String>>,
+    testing: bool,
+    new_url: String,
+    new_username: String,
+    new_password: String,
+    import_path: String,
+    /// Result of the last add, import or save.
+    status: Option<(String, bool)>,
+    unsaved: bool,
+    // States
+    rows: Vec<RowState>,
+    scroll_state: widget::scrollable::State,
+    new_url_state: widget::text_input::State,
+    new_username_state: widget::text_input::State,
+    new_password_state: widget::text_input::State,
+    add_state: widget::button::State,
+    import_path_state: widget::text_input::State,
+    import_state: widget::button::State,
+    save_state: widget::button::State,
+    test_state: widget::button::State,
+    remove_failed_state: widget::button::State,
+}
+
+fn editor_msg(msg: EditorMsg) -> Msg {
+    Msg::ProxyEditor(msg)
+}
+
+impl ProxyEditor {
+    pub fn new(config: &Config, list: ProxyList) -> Self {
+        Self {
+            path: config.proxies_file.clone(),
+            credentials_path: config.proxy_credentials_file.clone(),
+            rows: list.entries.iter().map(|_| Default::default()).collect(),
+            entries: list.entries,
+            credentials: list.credentials,
+            test_url: config.proxy_test_url.clone(),
+            test_timeout: config.proxy_test_timeout(),
+            test_results: HashMap::new(),
+            testing: false,
+            new_url: String::new(),
+            new_username: String::new(),
+            new_password: String::new(),
+            import_path: String::new(),
+            status: None,
+            unsaved: false,
+            scroll_state: Default::default(),
+            new_url_state: Default::default(),
+            new_username_state: Default::default(),
+            new_password_state: Default::default(),
+            add_state: Default::default(),
+            import_path_state: Default::default(),
+            import_state: Default::default(),
+            save_state: Default::default(),
+            test_state: Default::default(),
+            remove_failed_state: Default::default(),
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.status = Some((message, true));
+    }
+
+    fn info(&mut self, message: String) {
+        self.status = Some((message, false));
+    }
+
+    fn changed(&mut self) {
+        self.rows.resize_with(self.entries.len(), Default::default);
+        self.unsaved = true;
+    }
+
+    fn add(&mut self, entries: Vec<ProxyEntry>) -> usize {
+        let before = self.entries.len();
+        for mut entry in entries {
+            if self.entries.iter().any(|e| e.url == entry.url) {
+                continue;
+            }
+            if let Some(credentials) = entry.credentials.take() {
+                self.credentials.insert(entry.url.clone(), credentials);
+            }
+            self.entries.push(entry);
+        }
+        self.changed();
+        self.entries.len() - before
+    }
+
+    pub fn update(&mut self, msg: EditorMsg) -> Command<Msg> {
+        match msg {
+            EditorMsg::UrlChanged(url) => self.new_url = url,
+            EditorMsg::UsernameChanged(username) => self.new_username = username,
+            EditorMsg::PasswordChanged(password) => self.new_password = password,
+            EditorMsg::Add => {
+                let mut entry = ProxyEntry::from_url(self.new_url.trim());
+                if !self.new_username.is_empty() {
+                    entry.credentials = Some(Credentials {
+                        username: self.new_username.clone(),
+                        password: self.new_password.clone(),
+                    });
+                }
+                let url = entry.url.clone();
+                match entry.check() {
+                    Ok(()) if self.add(vec![entry]) == 0 => {
+                        self.error(format!("{} is already in the list", url))
+                    }
+                    Ok(()) => {
+                        self.new_url.clear();
+                        self.new_username.clear();
+                        self.new_password.clear();
+                        self.status = None;
+                    }
+                    Err(reason) => self.error(format!("Invalid proxy url: {}", reason)),
+                }
+            }
+            EditorMsg::Remove(i) if i < self.entries.len() => {
+                self.entries.remove(i);
+                self.changed();
+            }
+            EditorMsg::SetEnabled(i, enabled) if i < self.entries.len() => {
+                self.entries[i].enabled = enabled;
+                self.changed();
+            }
+            EditorMsg::MoveUp(i) if i > 0 && i < self.entries.len() => {
+                self.entries.swap(i - 1, i);
+                self.changed();
+            }
+            EditorMsg::MoveDown(i) if i + 1 < self.entries.len() => {
+                self.entries.swap(i, i + 1);
+                self.changed();
+            }
+            EditorMsg::Remove(_)
+            | EditorMsg::SetEnabled(..)
+            | EditorMsg::MoveUp(_)
+            | EditorMsg::MoveDown(_) => {}
+            EditorMsg::ImportPathChanged(path) => self.import_path = path,
+            EditorMsg::Import => {
+                let path = PathBuf::from(self.import_path.trim());
+                return Command::perform(
+                    async move { import_proxies_text(path).await.map_err(|e| e.to_string()) },
+                    |res| editor_msg(EditorMsg::Imported(res)),
+                );
+            }
+            EditorMsg::Imported(Ok((entries, errors))) => {
+                let found = entries.len();
+                let added = self.add(entries);
+                let mut message = format!(
+                    "Imported {} proxies ({} already in the list)",
+                    added,
+                    found - added
+                );
+                if !errors.is_empty() {
+                    let lines = errors
+                        .iter()
+                        .map(|(line, reason)| format!("line {}: {}", line, reason))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    message = format!("{}, skipped {}:\n{}", message, errors.len(), lines);
+                }
+                self.status = Some((message, !errors.is_empty()));
+            }
+            EditorMsg::Imported(Err(err)) => self.error(format!("Could not import: {}", err)),
+            EditorMsg::Save => {
+                let save = save_proxies_list(
+                    self.path.clone(),
+                    self.credentials_path.clone(),
+                    self.entries.clone(),
+                    self.credentials.clone(),
+                );
+                return Command::perform(
+                    async move { save.await.map_err(|e| e.to_string()) },
+                    |res| editor_msg(EditorMsg::Saved(res)),
+                );
+            }
+            EditorMsg::Saved(Ok(_)) => {
+                self.unsaved = false;
+                self.info(format!("Saved to {}", self.path.display()));
+            }
+            EditorMsg::Saved(Err(err)) => self.error(format!("Could not save: {}", err)),
+            EditorMsg::Test => {
+                self.testing = true;
+                self.test_results.clear();
+                let mut targets = Vec::new();
+                for entry in &self.entries {
+                    match &entry.invalid {
+                        None => targets.push(ProxyTarget::new(entry, &self.credentials)),
+                        Some(reason) => {
+                            self.test_results
+                                .insert(entry.url.clone(), Err(reason.clone()));
+                        }
+                    }
+                }
+                let test = test_proxies(targets.clone(), self.test_url.clone(), self.test_timeout);
+                return Command::perform(
+                    async move {
+                        let results = test.await;
+                        targets
+                            .into_iter()
+                            .map(|target| target.url)
+                            .zip(results)
+                            .collect()
+                    },
+                    |results| editor_msg(EditorMsg::Tested(results)),
+                );
+            }
+            EditorMsg::Tested(results) => {
+                self.testing = false;
+                self.test_results.extend(results);
+                let failed = self.test_results.values().filter(|r| r.is_err()).count();
+                let message = format!(
+                    "Tested {} proxies against {}: {} working, {} failed",
+                    self.test_results.len(),
+                    self.test_url,
+                    self.test_results.len() - failed,
+                    failed
+                );
+                self.status = Some((message, failed > 0));
+            }
+            EditorMsg::RemoveFailed => {
+                let results = &self.test_results;
+                self.entries
+                    .retain(|entry| !matches!(results.get(&entry.url), Some(Err(_))));
+                self.test_results.retain(|_, result| result.is_ok());
+                self.changed();
+            }
+        }
+        Command::none()
+    }
+
+    pub fn view(&mut self) -> Element<Msg> {
+        let count = self.entries.len();
+        let mut list = widget::Scrollable::new(&mut self.scroll_state)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .spacing(2);
+        for (i, (entry, row)) in self.entries.iter().zip(self.rows.iter_mut()).enumerate() {
+            let enabled = widget::Checkbox::new(entry.enabled, "", move |enabled| {
+                editor_msg(EditorMsg::SetEnabled(i, enabled))
+            });
+            let mut name = entry.name().to_string();
+            if entry.credentials.is_some() || self.credentials.contains_key(&entry.url) {
+                name.push_str(" (login)");
+            }
+            let mut url = widget::Text::new(name).width(Length::Fill);
+            // Entries loaded from the file may not pass the check
+            if entry.invalid.is_some() {
+                url = url.color(Color::from_rgb8(206, 10, 10));
+            }
+            let mut up = widget::Button::new(&mut row.up, widget::Text::new("^").size(14));
+            if i > 0 {
+                up = up.on_press(editor_msg(EditorMsg::MoveUp(i)));
+            }
+            let mut down = widget::Button::new(&mut row.down, widget::Text::new("v").size(14));
+            if i + 1 < count {
+                down = down.on_press(editor_msg(EditorMsg::MoveDown(i)));
+            }
+            let remove = widget::Button::new(&mut row.remove, widget::Text::new("x").size(14))
+                .on_press(editor_msg(EditorMsg::Remove(i)));
+            let result = match self.test_results.get(&entry.url) {
+                Some(Ok(latency)) => widget::Text::new(format!("{} ms", latency.as_millis()))
+                    .color(Color::from_rgb8(32, 160, 60)),
+                Some(Err(reason)) => widget::Text::new(format!("failed: {}", reason))
+                    .color(Color::from_rgb8(206, 10, 10)),
+                None => widget::Text::new(""),
+            };
+            list = list.push(
+                widget::Row::new()
+                    .push(enabled)
+                    .push(url)
+                    .push(result.size(14))
+                    .push(up)
+                    .push(down)
+                    .push(remove)
+                    .spacing(4)
+                    .align_items(iced::Align::Center),
+            );
+        }
+        let add_row = widget::Row::new()
+            .push(
+                widget::TextInput::new(
+                    &mut self.new_url_state,
+                    "socks5://host:port",
+                    &self.new_url,
+                    |url| editor_msg(EditorMsg::UrlChanged(url)),
+                )
+                .on_submit(editor_msg(EditorMsg::Add)),
+            )
+            .push(
+                widget::TextInput::new(
+                    &mut self.new_username_state,
+                    "Username",
+                    &self.new_username,
+                    |username| editor_msg(EditorMsg::UsernameChanged(username)),
+                )
+                .width(Length::Units(100))
+                .on_submit(editor_msg(EditorMsg::Add)),
+            )
+            .push(
+                widget::TextInput::new(
+                    &mut self.new_password_state,
+                    "Password",
+                    &self.new_password,
+                    |password| editor_msg(EditorMsg::PasswordChanged(password)),
+                )
+                .password()
+                .width(Length::Units(100))
+                .on_submit(editor_msg(EditorMsg::Add)),
+            )
+            .push(
+                widget::Button::new(&mut self.add_state, widget::Text::new("Add"))
+                    .on_press(editor_msg(EditorMsg::Add)),
+            )
+            .spacing(4);
+        let mut import = widget::Button::new(&mut self.import_state, widget::Text::new("Import"));
+        if !self.import_path.trim().is_empty() {
+            import = import.on_press(editor_msg(EditorMsg::Import));
+        }
+        let import_row = widget::Row::new()
+            .push(
+                widget::TextInput::new(
+                    &mut self.import_path_state,
+                    "Text file with one proxy per line",
+                    &self.import_path,
+                    |path| editor_msg(EditorMsg::ImportPathChanged(path)),
+                )
+                .on_submit(editor_msg(EditorMsg::Import)),
+            )
+            .push(import)
+            .spacing(4);
+        let mut save = widget::Button::new(
+            &mut self.save_state,
+            widget::Text::new(if self.unsaved { "Save *" } else { "Save" }),
+        );
+        if self.unsaved {
+            save = save.on_press(editor_msg(EditorMsg::Save));
+        }
+        let mut test = widget::Button::new(
+            &mut self.test_state,
+            widget::Text::new(if self.testing {
+                "Testing..."
+            } else {
+                "Test proxies"
+            }),
+        );
+        if !self.testing && count > 0 {
+            test = test.on_press(editor_msg(EditorMsg::Test));
+        }
+        let mut remove_failed = widget::Button::new(
+            &mut self.remove_failed_state,
+            widget::Text::new("Remove failed"),
+        );
+        if !self.testing && self.test_results.values().any(Result::is_err) {
+            remove_failed = remove_failed.on_press(editor_msg(EditorMsg::RemoveFailed));
+        }
+        let buttons = widget::Row::new()
+            .push(save)
+            .push(test)
+            .push(remove_failed)
+            .spacing(8);
+        let enabled = self.entries.iter().filter(|e| e.enabled).count();
+        let mut column = widget::Column::new()
+            .push(widget::Text::new(format!(
+                "{} proxies, {} enabled",
+                count, enabled
+            )))
+            .push(list)
+            .push(add_row)
+            .push(import_row)
+            .push(buttons)
+            .spacing(4)
+            .height(Length::Fill);
+        if let Some((status, is_error)) = &self.status {
+            let color = if *is_error {
+                Color::from_rgb8(206, 10, 10)
+            } else {
+                Color::BLACK
+            };
+            column = column.push(widget::Text::new(&status[..]).size(14).color(color));
+        }
+        column.into()
+    }
+}
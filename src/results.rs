@@ -0,0 +1,764 @@
+use crate::{
+    ids::{IdsSnapshot, SweepRange},
+    models::GroupInfoResponse,
+    GroupId,
+};
+use regex::Regex;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json as json;
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+lazy_static! {
+    static ref ROBUX_REGEX: Regex = Regex::new(r"^Group (\d+) has (\d+) robux.$").unwrap();
+}
+
+/// Results file written by older versions, imported into a new database.
+const ROBUX_FILE: &str = "robux.txt";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS groups (
+    id INTEGER PRIMARY KEY,
+    name TEXT,
+    robux INTEGER NOT NULL,
+    first_seen INTEGER NOT NULL,
+    last_seen INTEGER NOT NULL,
+    premium INTEGER NOT NULL DEFAULT 0,
+    visited INTEGER NOT NULL DEFAULT 0,
+    session INTEGER REFERENCES sessions(id)
+);
+CREATE TABLE IF NOT EXISTS group_details (
+    id INTEGER PRIMARY KEY REFERENCES groups(id),
+    info TEXT NOT NULL,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS group_snapshots (
+    id INTEGER NOT NULL REFERENCES groups(id),
+    checked_at INTEGER NOT NULL,
+    robux INTEGER NOT NULL,
+    owned INTEGER NOT NULL,
+    disqualified TEXT
+);
+CREATE INDEX IF NOT EXISTS group_snapshots_id ON group_snapshots (id, checked_at);
+CREATE TABLE IF NOT EXISTS checkpoints (
+    session INTEGER PRIMARY KEY REFERENCES sessions(id),
+    strategy TEXT NOT NULL,
+    max_group_id INTEGER NOT NULL DEFAULT 0,
+    sweep_start INTEGER NOT NULL DEFAULT 0,
+    sweep_end INTEGER NOT NULL DEFAULT 0,
+    sweep_stride INTEGER NOT NULL DEFAULT 1,
+    next_index INTEGER NOT NULL DEFAULT 0,
+    sweep_checked INTEGER NOT NULL DEFAULT 0,
+    unfinished TEXT NOT NULL DEFAULT '[]',
+    groups_checked INTEGER NOT NULL,
+    groups_found INTEGER NOT NULL,
+    robux_found INTEGER NOT NULL,
+    finished INTEGER NOT NULL DEFAULT 0,
+    updated_at INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS checked_ids (
+    session INTEGER NOT NULL REFERENCES sessions(id),
+    id INTEGER NOT NULL,
+    PRIMARY KEY (session, id)
+) WITHOUT ROWID;
+";
+
+/// A group stored in the results database. Times are seconds since the Unix epoch.
+#[derive(Debug, Clone)]
+pub struct StoredGroup {
+    pub id: GroupId,
+    pub name: Option<String>,
+    pub robux: u32,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    pub premium: bool,
+    pub visited: bool,
+    /// The scan session that first found the group.
+    pub session: Option<i64>,
+    /// The group info fetched when the group was last found. Missing for groups
+    /// found by older versions.
+    pub details: Option<GroupInfoResponse>,
+    /// Every time the group was found or checked again, oldest first.
+    pub history: Vec<GroupSnapshot>,
+}
+
+impl StoredGroup {
+    /// Why the group was no longer a hit when it was last checked.
+    pub fn disqualified(&self) -> Option<&str> {
+        self.history.last()?.disqualified.as_deref()
+    }
+}
+
+/// A group's funds and owner status when it was found or checked again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupSnapshot {
+    pub checked_at: i64,
+    pub robux: u32,
+    pub owned: bool,
+    /// Why the group was not a hit anymore, `None` if it still was.
+    pub disqualified: Option<String>,
+}
+
+impl GroupSnapshot {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            checked_at: row.get(0)?,
+            robux: row.get(1)?,
+            owned: row.get(2)?,
+            disqualified: row.get(3)?,
+        })
+    }
+}
+
+/// Counters of a scan session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    pub groups_checked: u64,
+    pub groups_found: u64,
+    pub robux_found: u64,
+}
+
+/// Saved progress of a scan session, used to resume it.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub session: i64,
+    pub started_at: i64,
+    pub updated_at: i64,
+    pub ids: IdsSnapshot,
+    pub progress: Progress,
+    /// Every id has been checked, so there is nothing left to resume.
+    pub finished: bool,
+}
+
+pub fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Formats a time saved in the database in the local time zone.
+pub fn format_unix_time(secs: i64) -> String {
+    use chrono::TimeZone;
+    match chrono::Local.timestamp_opt(secs, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => secs.to_string(),
+    }
+}
+
+fn insert_snapshot(
+    conn: &Connection,
+    id: GroupId,
+    snapshot: &GroupSnapshot,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO group_snapshots (id, checked_at, robux, owned, disqualified)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            id,
+            snapshot.checked_at,
+            snapshot.robux,
+            snapshot.owned,
+            snapshot.disqualified
+        ],
+    )
+    .map(drop)
+}
+
+/// Handle to the results database, shared between scan workers.
+#[derive(Clone)]
+pub struct Results {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Results {
+    /// Opens or creates the database, importing `robux.txt` the first time.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        let results = Self::with_schema(Connection::open(path)?)?;
+        results.import_robux_file(Path::new(ROBUX_FILE))?;
+        Ok(results)
+    }
+
+    fn with_schema(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// An empty database that is gone once dropped.
+    #[cfg(test)]
+    pub(crate) fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_schema(Connection::open_in_memory()?)
+    }
+
+    fn import_robux_file(&self, path: &Path) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let empty = conn
+            .query_row("SELECT id FROM groups LIMIT 1", params![], |row| {
+                row.get::<_, i64>(0)
+            })
+            .optional()?
+            .is_none();
+        let file = match std::fs::read_to_string(path) {
+            Ok(file) if empty => file,
+            _ => return Ok(()),
+        };
+        let now = unix_time();
+        let tx = conn.transaction()?;
+        for c in file.lines().filter_map(|s| ROBUX_REGEX.captures(s)) {
+            let (gid, robux) = match (c[1].parse::<GroupId>(), c[2].parse::<u32>()) {
+                (Ok(gid), Ok(robux)) => (gid, robux),
+                _ => continue,
+            };
+            tx.execute(
+                "INSERT OR IGNORE INTO groups (id, robux, first_seen, last_seen)
+                 VALUES (?1, ?2, ?3, ?3)",
+                params![gid, robux, now],
+            )?;
+        }
+        tx.commit()
+    }
+
+    async fn with_conn<T, F>(&self, f: F) -> rusqlite::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || f(&conn.lock().unwrap()))
+            .await
+            .expect("results database task panicked")
+    }
+
+    pub fn start_session(&self) -> rusqlite::Result<Session> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO sessions (started_at) VALUES (?1)",
+            params![unix_time()],
+        )?;
+        Ok(Session {
+            results: self.clone(),
+            id: conn.last_insert_rowid(),
+        })
+    }
+
+    /// Continues a session from its checkpoint.
+    pub fn resume_session(&self, checkpoint: &Checkpoint) -> Session {
+        Session {
+            results: self.clone(),
+            id: checkpoint.session,
+        }
+    }
+
+    /// The most recently saved checkpoint of a session that has not finished.
+    pub async fn last_checkpoint(&self) -> rusqlite::Result<Option<Checkpoint>> {
+        self.with_conn(|conn| {
+            let checkpoint = conn
+                .query_row(
+                    "SELECT c.session, s.started_at, c.updated_at, c.strategy, c.max_group_id,
+                            c.sweep_start, c.sweep_end, c.sweep_stride, c.next_index,
+                            c.sweep_checked, c.unfinished, c.groups_checked, c.groups_found,
+                            c.robux_found
+                     FROM checkpoints c JOIN sessions s ON s.id = c.session
+                     WHERE c.finished = 0
+                     ORDER BY c.updated_at DESC, c.session DESC LIMIT 1",
+                    params![],
+                    |row| {
+                        let strategy: String = row.get(3)?;
+                        let ids = if strategy == "sweep" {
+                            let unfinished: String = row.get(10)?;
+                            IdsSnapshot::Sweep {
+                                range: SweepRange {
+                                    start: row.get(5)?,
+                                    end: row.get(6)?,
+                                    stride: row.get(7)?,
+                                },
+                                next_index: row.get::<_, i64>(8)? as u64,
+                                checked: row.get::<_, i64>(9)? as u64,
+                                unfinished: json::from_str(&unfinished).unwrap_or_default(),
+                            }
+                        } else {
+                            IdsSnapshot::Random {
+                                max: row.get(4)?,
+                                checked: Vec::new(),
+                            }
+                        };
+                        Ok(Checkpoint {
+                            session: row.get(0)?,
+                            started_at: row.get(1)?,
+                            updated_at: row.get(2)?,
+                            ids,
+                            progress: Progress {
+                                groups_checked: row.get::<_, i64>(11)? as u64,
+                                groups_found: row.get::<_, i64>(12)? as u64,
+                                robux_found: row.get::<_, i64>(13)? as u64,
+                            },
+                            finished: false,
+                        })
+                    },
+                )
+                .optional()?;
+            let mut checkpoint = match checkpoint {
+                Some(checkpoint) => checkpoint,
+                None => return Ok(None),
+            };
+            if let IdsSnapshot::Random { checked, .. } = &mut checkpoint.ids {
+                let mut stmt = conn.prepare("SELECT id FROM checked_ids WHERE session = ?1")?;
+                let ids = stmt.query_map(params![checkpoint.session], |row| row.get(0))?;
+                *checked = ids.collect::<rusqlite::Result<_>>()?;
+            }
+            Ok(Some(checkpoint))
+        })
+        .await
+    }
+
+    /// All stored groups, richest first.
+    pub async fn groups(&self) -> rusqlite::Result<Vec<StoredGroup>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT g.id, g.name, g.robux, g.first_seen, g.last_seen, g.premium, g.visited,
+                        g.session, d.info
+                 FROM groups g LEFT JOIN group_details d ON d.id = g.id
+                 ORDER BY g.robux DESC",
+            )?;
+            let rows = stmt.query_map(params![], |row| {
+                let details: Option<String> = row.get(8)?;
+                Ok(StoredGroup {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    robux: row.get(2)?,
+                    first_seen: row.get(3)?,
+                    last_seen: row.get(4)?,
+                    premium: row.get(5)?,
+                    visited: row.get(6)?,
+                    session: row.get(7)?,
+                    details: details.and_then(|info| json::from_str(&info).ok()),
+                    history: Vec::new(),
+                })
+            })?;
+            let mut groups = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+            let mut stmt = conn.prepare(
+                "SELECT checked_at, robux, owned, disqualified, id FROM group_snapshots
+                 ORDER BY checked_at, rowid",
+            )?;
+            let mut history = HashMap::<GroupId, Vec<GroupSnapshot>>::new();
+            let mut rows = stmt.query(params![])?;
+            while let Some(row) = rows.next()? {
+                history
+                    .entry(row.get(4)?)
+                    .or_default()
+                    .push(GroupSnapshot::from_row(row)?);
+            }
+            for group in &mut groups {
+                group.history = history.remove(&group.id).unwrap_or_default();
+            }
+            Ok(groups)
+        })
+        .await
+    }
+
+    /// Saves the result of checking a stored group again. Groups found before
+    /// snapshots were kept first get one for when they were last found, so
+    /// the change shows.
+    pub async fn record_check(
+        &self,
+        info: GroupInfoResponse,
+        robux: u32,
+        disqualified: Option<String>,
+    ) -> rusqlite::Result<GroupSnapshot> {
+        self.with_conn(move |conn| {
+            let tx = conn.unchecked_transaction()?;
+            let id = info.id;
+            let has_history = tx
+                .query_row(
+                    "SELECT 1 FROM group_snapshots WHERE id = ?1 LIMIT 1",
+                    params![id],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some();
+            if !has_history {
+                let found = tx
+                    .query_row(
+                        "SELECT last_seen, robux FROM groups WHERE id = ?1",
+                        params![id],
+                        |row| {
+                            Ok(GroupSnapshot {
+                                checked_at: row.get(0)?,
+                                robux: row.get(1)?,
+                                owned: false,
+                                disqualified: None,
+                            })
+                        },
+                    )
+                    .optional()?;
+                if let Some(found) = found {
+                    insert_snapshot(&tx, id, &found)?;
+                }
+            }
+            let snapshot = GroupSnapshot {
+                checked_at: unix_time(),
+                robux,
+                owned: info.owner.is_some(),
+                disqualified,
+            };
+            insert_snapshot(&tx, id, &snapshot)?;
+            tx.execute(
+                "UPDATE groups SET name = ?2, robux = ?3, premium = ?4 WHERE id = ?1",
+                params![id, info.name, robux, info.is_builders_club_only],
+            )?;
+            tx.execute(
+                "INSERT OR REPLACE INTO group_details (id, info, updated_at)
+                 VALUES (?1, ?2, ?3)",
+                params![id, json::to_string(&info).unwrap(), snapshot.checked_at],
+            )?;
+            tx.commit()?;
+            Ok(snapshot)
+        })
+        .await
+    }
+
+    pub async fn set_visited(&self, id: GroupId, visited: bool) -> rusqlite::Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "UPDATE groups SET visited = ?2 WHERE id = ?1",
+                params![id, visited],
+            )
+            .map(drop)
+        })
+        .await
+    }
+}
+
+/// A single scan's view of the results database.
+#[derive(Clone)]
+pub struct Session {
+    results: Results,
+    id: i64,
+}
+
+impl Session {
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn results(&self) -> &Results {
+        &self.results
+    }
+
+    /// Saves the session's progress. The ids of a random snapshot are added
+    /// to the ones saved before.
+    pub async fn save_checkpoint(
+        &self,
+        ids: IdsSnapshot,
+        progress: Progress,
+        finished: bool,
+    ) -> rusqlite::Result<()> {
+        let session = self.id;
+        self.results
+            .with_conn(move |conn| {
+                let tx = conn.unchecked_transaction()?;
+                let (strategy, max_group_id, range, next_index, sweep_checked, unfinished) =
+                    match &ids {
+                        IdsSnapshot::Random { max, checked } => {
+                            for id in checked {
+                                tx.execute(
+                                    "INSERT OR IGNORE INTO checked_ids (session, id) VALUES (?1, ?2)",
+                                    params![session, id],
+                                )?;
+                            }
+                            ("random", *max, None, 0, 0, Vec::new())
+                        }
+                        IdsSnapshot::Sweep {
+                            range,
+                            next_index,
+                            checked,
+                            unfinished,
+                        } => (
+                            "sweep",
+                            0,
+                            Some(*range),
+                            *next_index,
+                            *checked,
+                            unfinished.clone(),
+                        ),
+                    };
+                let range = range.unwrap_or(SweepRange {
+                    start: 0,
+                    end: 0,
+                    stride: 1,
+                });
+                tx.execute(
+                    "INSERT OR REPLACE INTO checkpoints (session, strategy, max_group_id,
+                         sweep_start, sweep_end, sweep_stride, next_index, sweep_checked,
+                         unfinished, groups_checked, groups_found, robux_found, finished,
+                         updated_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                    params![
+                        session,
+                        strategy,
+                        max_group_id,
+                        range.start,
+                        range.end,
+                        range.stride,
+                        next_index as i64,
+                        sweep_checked as i64,
+                        json::to_string(&unfinished).unwrap(),
+                        progress.groups_checked as i64,
+                        progress.groups_found as i64,
+                        progress.robux_found as i64,
+                        finished,
+                        unix_time()
+                    ],
+                )?;
+                tx.commit()
+            })
+            .await
+    }
+
+    /// Inserts a found group with its info, or refreshes it if it was found before.
+    pub async fn record_group(&self, robux: u32, info: GroupInfoResponse) -> rusqlite::Result<()> {
+        let session = self.id;
+        self.results
+            .with_conn(move |conn| {
+                let now = unix_time();
+                let tx = conn.unchecked_transaction()?;
+                tx.execute(
+                    "INSERT INTO groups (id, name, robux, first_seen, last_seen, premium, session)
+                     VALUES (?1, ?2, ?3, ?4, ?4, ?5, ?6)
+                     ON CONFLICT(id) DO UPDATE SET
+                         name = COALESCE(excluded.name, name),
+                         robux = excluded.robux,
+                         last_seen = excluded.last_seen,
+                         premium = excluded.premium",
+                    params![
+                        info.id,
+                        info.name,
+                        robux,
+                        now,
+                        info.is_builders_club_only,
+                        session
+                    ],
+                )?;
+                tx.execute(
+                    "INSERT OR REPLACE INTO group_details (id, info, updated_at)
+                     VALUES (?1, ?2, ?3)",
+                    params![info.id, json::to_string(&info).unwrap(), now],
+                )?;
+                let snapshot = GroupSnapshot {
+                    checked_at: now,
+                    robux,
+                    owned: info.owner.is_some(),
+                    disqualified: None,
+                };
+                insert_snapshot(&tx, info.id, &snapshot)?;
+                tx.commit()
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GroupOwner;
+
+    fn group(id: GroupId, name: &str) -> GroupInfoResponse {
+        GroupInfoResponse {
+            id,
+            name: name.to_string(),
+            description: None,
+            owner: None,
+            shout: None,
+            member_count: Some(3),
+            is_builders_club_only: false,
+            public_entry_allowed: true,
+            is_locked: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn records_a_group_found_again_in_place() {
+        let results = Results::open_in_memory().unwrap();
+        let session = results.start_session().unwrap();
+        session.record_group(50, group(1, "Before")).await.unwrap();
+        let mut renamed = group(1, "After");
+        renamed.owner = Some(GroupOwner {
+            user_id: 2,
+            username: "bob".to_string(),
+            display_name: None,
+        });
+        session.record_group(70, renamed).await.unwrap();
+
+        let groups = results.groups().await.unwrap();
+        assert_eq!(groups.len(), 1);
+        let stored = &groups[0];
+        assert_eq!(stored.id, 1);
+        assert_eq!(stored.name.as_deref(), Some("After"));
+        assert_eq!(stored.robux, 70);
+        assert_eq!(stored.session, Some(session.id()));
+        assert!(stored.first_seen <= stored.last_seen);
+        assert_eq!(stored.details.as_ref().unwrap().name, "After");
+        let history: Vec<_> = stored.history.iter().map(|s| (s.robux, s.owned)).collect();
+        assert_eq!(history, vec![(50, false), (70, true)]);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_visited_flag() {
+        let results = Results::open_in_memory().unwrap();
+        let session = results.start_session().unwrap();
+        session.record_group(50, group(1, "One")).await.unwrap();
+        session.record_group(60, group(2, "Two")).await.unwrap();
+        results.set_visited(1, true).await.unwrap();
+
+        let visited = |groups: Vec<StoredGroup>| {
+            let mut visited: Vec<_> = groups.iter().map(|g| (g.id, g.visited)).collect();
+            visited.sort_unstable();
+            visited
+        };
+        assert_eq!(
+            visited(results.groups().await.unwrap()),
+            vec![(1, true), (2, false)]
+        );
+        // Finding it again does not reset it
+        session.record_group(80, group(1, "One")).await.unwrap();
+        assert_eq!(
+            visited(results.groups().await.unwrap()),
+            vec![(1, true), (2, false)]
+        );
+        results.set_visited(1, false).await.unwrap();
+        assert_eq!(
+            visited(results.groups().await.unwrap()),
+            vec![(1, false), (2, false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn imports_the_robux_file_once() {
+        let path =
+            std::env::temp_dir().join(format!("groupfinder-robux-{}.txt", std::process::id()));
+        std::fs::write(
+            &path,
+            "Group 5 has 120 robux.\nnot a group\nGroup 6 has 3 robux.\nGroup x has 1 robux.\n",
+        )
+        .unwrap();
+        let results = Results::open_in_memory().unwrap();
+        results.import_robux_file(&path).unwrap();
+        let mut groups: Vec<_> = results
+            .groups()
+            .await
+            .unwrap()
+            .iter()
+            .map(|g| (g.id, g.robux, g.name.clone(), g.session))
+            .collect();
+        groups.sort_unstable();
+        assert_eq!(groups, vec![(5, 120, None, None), (6, 3, None, None)]);
+
+        // Only an empty database imports it
+        std::fs::write(&path, "Group 7 has 9 robux.\n").unwrap();
+        results.import_robux_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(results.groups().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn resumes_a_sweep_from_its_checkpoint() {
+        let results = Results::open_in_memory().unwrap();
+        let session = results.start_session().unwrap();
+        let ids = IdsSnapshot::Sweep {
+            range: SweepRange {
+                start: 10,
+                end: 500,
+                stride: 3,
+            },
+            next_index: 40,
+            checked: 35,
+            unfinished: vec![127, 115],
+        };
+        let progress = Progress {
+            groups_checked: 35,
+            groups_found: 2,
+            robux_found: 90,
+        };
+        session
+            .save_checkpoint(ids.clone(), progress, false)
+            .await
+            .unwrap();
+
+        let checkpoint = results.last_checkpoint().await.unwrap().unwrap();
+        assert_eq!(checkpoint.session, session.id());
+        assert_eq!(checkpoint.ids, ids);
+        assert_eq!(checkpoint.progress, progress);
+        assert!(!checkpoint.finished);
+    }
+
+    #[tokio::test]
+    async fn resumes_a_random_scan_with_every_checked_id() {
+        let results = Results::open_in_memory().unwrap();
+        let session = results.start_session().unwrap();
+        // Each snapshot only holds the ids checked since the one before
+        for checked in &[vec![4, 8], vec![15], vec![8, 16]] {
+            let ids = IdsSnapshot::Random {
+                max: 100,
+                checked: checked.clone(),
+            };
+            session
+                .save_checkpoint(ids, Progress::default(), false)
+                .await
+                .unwrap();
+        }
+
+        let checkpoint = results.last_checkpoint().await.unwrap().unwrap();
+        match checkpoint.ids {
+            IdsSnapshot::Random { max, mut checked } => {
+                assert_eq!(max, 100);
+                checked.sort_unstable();
+                assert_eq!(checked, vec![4, 8, 15, 16]);
+            }
+            IdsSnapshot::Sweep { .. } => panic!("expected a random checkpoint"),
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_resume_finished_sessions() {
+        let results = Results::open_in_memory().unwrap();
+        let ids = IdsSnapshot::Random {
+            max: 100,
+            checked: Vec::new(),
+        };
+        let older = results.start_session().unwrap();
+        older
+            .save_checkpoint(ids.clone(), Progress::default(), false)
+            .await
+            .unwrap();
+        let newer = results.start_session().unwrap();
+        newer
+            .save_checkpoint(ids.clone(), Progress::default(), false)
+            .await
+            .unwrap();
+        let checkpoint = results.last_checkpoint().await.unwrap().unwrap();
+        assert_eq!(checkpoint.session, newer.id());
+
+        newer
+            .save_checkpoint(ids.clone(), Progress::default(), true)
+            .await
+            .unwrap();
+        let checkpoint = results.last_checkpoint().await.unwrap().unwrap();
+        assert_eq!(checkpoint.session, older.id());
+
+        older
+            .save_checkpoint(ids, Progress::default(), true)
+            .await
+            .unwrap();
+        assert!(results.last_checkpoint().await.unwrap().is_none());
+    }
+}
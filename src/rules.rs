@@ -0,0 +1,745 @@
+use crate::{models::GroupInfoResponse, GroupId};
+use std::{fmt, str::FromStr};
+
+/// Used when the config does not set `group_rule`: open groups nobody owns.
+pub const DEFAULT_RULE: &str = "public && !locked && !owned";
+
+/// A value a rule works with.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Value {
+    Number(i64),
+    Bool(bool),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Number,
+    Bool,
+    Text,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Type::Number => "a number",
+            Type::Bool => "true or false",
+            Type::Text => "text",
+        })
+    }
+}
+
+/// A property of a group that rules can test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    Robux,
+    MemberCount,
+    Public,
+    Locked,
+    Owned,
+    Premium,
+    HasShout,
+    Name,
+    Description,
+}
+
+impl Field {
+    pub const ALL: [Field; 10] = [
+        Field::Id,
+        Field::Robux,
+        Field::MemberCount,
+        Field::Public,
+        Field::Locked,
+        Field::Owned,
+        Field::Premium,
+        Field::HasShout,
+        Field::Name,
+        Field::Description,
+    ];
+
+    /// How the field is written in a rule.
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::Id => "id",
+            Field::Robux => "robux",
+            Field::MemberCount => "member_count",
+            Field::Public => "public",
+            Field::Locked => "locked",
+            Field::Owned => "owned",
+            Field::Premium => "premium",
+            Field::HasShout => "has_shout",
+            Field::Name => "name",
+            Field::Description => "description",
+        }
+    }
+
+    fn value_type(self) -> Type {
+        match self {
+            Field::Id | Field::Robux | Field::MemberCount => Type::Number,
+            Field::Name | Field::Description => Type::Text,
+            _ => Type::Bool,
+        }
+    }
+
+    /// `None` for fields from the group info while it has not been fetched.
+    fn value(self, group: &GroupRecord) -> Option<Value> {
+        let info = match self {
+            Field::Id => return Some(Value::Number(group.id as i64)),
+            Field::Robux => return Some(Value::Number(group.robux as i64)),
+            _ => group.info?,
+        };
+        Some(match self {
+            Field::MemberCount => Value::Number(info.member_count.unwrap_or(0) as i64),
+            Field::Public => Value::Bool(info.public_entry_allowed),
+            Field::Locked => Value::Bool(info.locked()),
+            Field::Owned => Value::Bool(info.owner.is_some()),
+            Field::Premium => Value::Bool(info.is_builders_club_only),
+            Field::HasShout => Value::Bool(info.shout.is_some()),
+            Field::Name => Value::Text(info.name.clone()),
+            Field::Description => Value::Text(info.description.clone().unwrap_or_default()),
+            Field::Id | Field::Robux => unreachable!(),
+        })
+    }
+}
+
+/// What is known about a group when a rule is evaluated. The group info is
+/// only fetched once the funds are known, and only if the rule could still
+/// match.
+#[derive(Debug, Clone, Copy)]
+pub struct GroupRecord<'a> {
+    pub id: GroupId,
+    pub robux: u32,
+    pub info: Option<&'a GroupInfoResponse>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+}
+
+impl CompareOp {
+    fn apply(self, left: &Value, right: &Value) -> bool {
+        match (self, left, right) {
+            (CompareOp::Contains, Value::Text(left), Value::Text(right)) => {
+                left.to_lowercase().contains(&right.to_lowercase())
+            }
+            (CompareOp::Contains, ..) => false,
+            (CompareOp::Eq, ..) => left == right,
+            (CompareOp::Ne, ..) => left != right,
+            (CompareOp::Lt, ..) => left < right,
+            (CompareOp::Le, ..) => left <= right,
+            (CompareOp::Gt, ..) => left > right,
+            (CompareOp::Ge, ..) => left >= right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Literal(Value),
+    Field(Field),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+}
+
+impl Expr {
+    /// `None` while a field it needs is unknown. `&&` and `||` still decide
+    /// when the known side is enough, like `false && x`.
+    fn evaluate(&self, group: &GroupRecord) -> Option<Value> {
+        match self {
+            Expr::Literal(value) => Some(value.clone()),
+            Expr::Field(field) => field.value(group),
+            Expr::Not(expr) => match expr.evaluate(group)? {
+                Value::Bool(b) => Some(Value::Bool(!b)),
+                _ => None,
+            },
+            Expr::And(left, right) => match (left.evaluate(group), right.evaluate(group)) {
+                (Some(Value::Bool(false)), _) | (_, Some(Value::Bool(false))) => {
+                    Some(Value::Bool(false))
+                }
+                (Some(Value::Bool(true)), Some(Value::Bool(true))) => Some(Value::Bool(true)),
+                _ => None,
+            },
+            Expr::Or(left, right) => match (left.evaluate(group), right.evaluate(group)) {
+                (Some(Value::Bool(true)), _) | (_, Some(Value::Bool(true))) => {
+                    Some(Value::Bool(true))
+                }
+                (Some(Value::Bool(false)), Some(Value::Bool(false))) => Some(Value::Bool(false)),
+                _ => None,
+            },
+            Expr::Compare(left, op, right) => {
+                let left = left.evaluate(group)?;
+                let right = right.evaluate(group)?;
+                Some(Value::Bool(op.apply(&left, &right)))
+            }
+        }
+    }
+}
+
+/// Why a rule could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleError {
+    /// Character of the rule the error is at, starting from 1.
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RuleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for RuleError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Text(String),
+    Word(String),
+    Not,
+    And,
+    Or,
+    Compare(CompareOp),
+    Open,
+    Close,
+    End,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "`{}`", n),
+            Token::Text(text) => write!(f, "{:?}", text),
+            Token::Word(word) => write!(f, "`{}`", word),
+            Token::Not => f.write_str("`!`"),
+            Token::And => f.write_str("`&&`"),
+            Token::Or => f.write_str("`||`"),
+            Token::Compare(_) => f.write_str("a comparison"),
+            Token::Open => f.write_str("`(`"),
+            Token::Close => f.write_str("`)`"),
+            Token::End => f.write_str("the end of the rule"),
+        }
+    }
+}
+
+/// Splits a rule into tokens, each with the column it starts at.
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, RuleError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let error = |message: String| RuleError { column, message };
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match (chars[i], next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (Token::Open, 1),
+            (')', _) => (Token::Close, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Compare(CompareOp::Eq), 2),
+            ('!', Some('=')) => (Token::Compare(CompareOp::Ne), 2),
+            ('<', Some('=')) => (Token::Compare(CompareOp::Le), 2),
+            ('>', Some('=')) => (Token::Compare(CompareOp::Ge), 2),
+            ('!', _) => (Token::Not, 1),
+            ('<', _) => (Token::Compare(CompareOp::Lt), 1),
+            ('>', _) => (Token::Compare(CompareOp::Gt), 1),
+            ('"', _) => {
+                let len = chars[i + 1..]
+                    .iter()
+                    .position(|&c| c == '"')
+                    .ok_or_else(|| error("the text is missing its closing `\"`".to_string()))?;
+                let text = chars[i + 1..i + 1 + len].iter().collect();
+                (Token::Text(text), len + 2)
+            }
+            (c, _) if c.is_ascii_digit() => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == '_')
+                    .count();
+                let digits: String = chars[i..i + len].iter().filter(|&&c| c != '_').collect();
+                let number = digits
+                    .parse()
+                    .map_err(|_| error(format!("`{}` is too large", digits)))?;
+                (Token::Number(number), len)
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || **c == '_')
+                    .count();
+                let word: String = chars[i..i + len].iter().collect();
+                let token = match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "contains" => Token::Compare(CompareOp::Contains),
+                    _ => Token::Word(word),
+                };
+                (token, len)
+            }
+            (c, _) => return Err(error(format!("unexpected `{}`", c))),
+        };
+        tokens.push((column, token));
+        i += len;
+    }
+    tokens.push((chars.len() + 1, Token::End));
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens. From loosest to tightest binding:
+/// `||`, `&&`, `!`, comparisons.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.next].1
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.next].0
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.next].1.clone();
+        if token != Token::End {
+            self.next += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, column: usize, message: String) -> Result<T, RuleError> {
+        Err(RuleError { column, message })
+    }
+
+    fn expect_bool(&self, column: usize, found: Type, what: &str) -> Result<(), RuleError> {
+        if found == Type::Bool {
+            Ok(())
+        } else {
+            self.error(
+                column,
+                format!("{} needs true or false, not {}", what, found),
+            )
+        }
+    }
+
+    fn or(&mut self) -> Result<(Expr, Type), RuleError> {
+        let column = self.column();
+        let (mut expr, ty) = self.and()?;
+        while *self.peek() == Token::Or {
+            self.expect_bool(column, ty, "`||`")?;
+            self.advance();
+            let column = self.column();
+            let (right, ty) = self.and()?;
+            self.expect_bool(column, ty, "`||`")?;
+            expr = Expr::Or(Box::new(expr), Box::new(right));
+        }
+        Ok((expr, ty))
+    }
+
+    fn and(&mut self) -> Result<(Expr, Type), RuleError> {
+        let column = self.column();
+        let (mut expr, ty) = self.not()?;
+        while *self.peek() == Token::And {
+            self.expect_bool(column, ty, "`&&`")?;
+            self.advance();
+            let column = self.column();
+            let (right, ty) = self.not()?;
+            self.expect_bool(column, ty, "`&&`")?;
+            expr = Expr::And(Box::new(expr), Box::new(right));
+        }
+        Ok((expr, ty))
+    }
+
+    fn not(&mut self) -> Result<(Expr, Type), RuleError> {
+        if *self.peek() != Token::Not {
+            return self.comparison();
+        }
+        self.advance();
+        let column = self.column();
+        let (expr, ty) = self.not()?;
+        self.expect_bool(column, ty, "`!`")?;
+        Ok((Expr::Not(Box::new(expr)), Type::Bool))
+    }
+
+    fn comparison(&mut self) -> Result<(Expr, Type), RuleError> {
+        let (left, left_type) = self.primary()?;
+        let op = match self.peek() {
+            Token::Compare(op) => *op,
+            _ => return Ok((left, left_type)),
+        };
+        let column = self.column();
+        self.advance();
+        let (right, right_type) = self.primary()?;
+        if left_type != right_type {
+            return self.error(
+                column,
+                format!("cannot compare {} with {}", left_type, right_type),
+            );
+        }
+        let ordered = matches!(
+            op,
+            CompareOp::Lt | CompareOp::Le | CompareOp::Gt | CompareOp::Ge
+        );
+        if ordered && left_type != Type::Number {
+            return self.error(
+                column,
+                format!("only numbers can be ordered, not {}", left_type),
+            );
+        }
+        if op == CompareOp::Contains && left_type != Type::Text {
+            return self.error(column, format!("`contains` needs text, not {}", left_type));
+        }
+        Ok((
+            Expr::Compare(Box::new(left), op, Box::new(right)),
+            Type::Bool,
+        ))
+    }
+
+    fn primary(&mut self) -> Result<(Expr, Type), RuleError> {
+        let column = self.column();
+        match self.advance() {
+            Token::Number(n) => Ok((Expr::Literal(Value::Number(n)), Type::Number)),
+            Token::Text(text) => Ok((Expr::Literal(Value::Text(text)), Type::Text)),
+            Token::Word(word) if word == "true" || word == "false" => {
+                Ok((Expr::Literal(Value::Bool(word == "true")), Type::Bool))
+            }
+            Token::Word(word) => match Field::ALL.iter().find(|f| f.name() == word) {
+                Some(&field) => Ok((Expr::Field(field), field.value_type())),
+                None => self.error(
+                    column,
+                    format!(
+                        "unknown field `{}`, expected one of {}",
+                        word,
+                        Field::ALL
+                            .iter()
+                            .map(|f| f.name())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                ),
+            },
+            Token::Open => {
+                let expr = self.or()?;
+                let column = self.column();
+                match self.advance() {
+                    Token::Close => Ok(expr),
+                    token => self.error(column, format!("expected `)`, found {}", token)),
+                }
+            }
+            token => self.error(
+                column,
+                format!("expected a field or value, found {}", token),
+            ),
+        }
+    }
+}
+
+/// Decides which groups are reported, e.g.
+/// `robux >= 100 && public && !locked && member_count < 50`.
+///
+/// Fields are combined with `&&`, `||` and `!` (or `and`, `or`, `not`),
+/// compared with `==`, `!=`, `<`, `<=`, `>`, `>=`, and text is searched with
+/// `contains`. An empty rule matches every group.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    source: String,
+    expr: Expr,
+}
+
+impl FromStr for Rule {
+    type Err = RuleError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let source = source.trim();
+        if source.is_empty() {
+            return Ok(Rule {
+                source: String::new(),
+                expr: Expr::Literal(Value::Bool(true)),
+            });
+        }
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            next: 0,
+        };
+        let (expr, ty) = parser.or()?;
+        if *parser.peek() != Token::End {
+            let column = parser.column();
+            let token = parser.advance();
+            return parser.error(column, format!("expected `&&` or `||`, found {}", token));
+        }
+        if ty != Type::Bool {
+            return parser.error(1, format!("the rule must be true or false, not {}", ty));
+        }
+        Ok(Rule {
+            source: source.to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Default for Rule {
+    fn default() -> Self {
+        DEFAULT_RULE.parse().unwrap()
+    }
+}
+
+impl Rule {
+    /// Whether the group is a hit, or `None` if that depends on the group
+    /// info, which has not been fetched yet.
+    pub fn matches(&self, group: &GroupRecord) -> Option<bool> {
+        match self.expr.evaluate(group) {
+            Some(Value::Bool(b)) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+/// The robux a group must have to be reported, checked before the rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RobuxRange {
+    pub minimum: u32,
+    /// No upper limit when `None`.
+    pub maximum: Option<u32>,
+}
+
+impl RobuxRange {
+    pub fn new(minimum: u32, maximum: Option<u32>) -> Result<Self, &'static str> {
+        match maximum {
+            Some(maximum) if maximum < minimum => {
+                Err("the maximum must be at least the minimum robux")
+            }
+            _ => Ok(Self { minimum, maximum }),
+        }
+    }
+
+    pub fn contains(&self, robux: u32) -> bool {
+        match self.maximum {
+            Some(maximum) => (self.minimum..=maximum).contains(&robux),
+            None => robux >= self.minimum,
+        }
+    }
+}
+
+impl fmt::Display for RobuxRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.maximum {
+            Some(maximum) => write!(f, "between {} and {} robux", self.minimum, maximum),
+            None => write!(f, "at least {} robux", self.minimum),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GroupOwner;
+
+    fn info() -> GroupInfoResponse {
+        GroupInfoResponse {
+            id: 7,
+            name: "Trading Hub".to_string(),
+            description: Some("Cheap limiteds".to_string()),
+            owner: None,
+            shout: None,
+            member_count: Some(12),
+            is_builders_club_only: false,
+            public_entry_allowed: true,
+            is_locked: None,
+        }
+    }
+
+    fn matches(rule: &str, robux: u32, info: Option<&GroupInfoResponse>) -> Option<bool> {
+        let rule: Rule = rule.parse().unwrap();
+        rule.matches(&GroupRecord { id: 7, robux, info })
+    }
+
+    fn error(rule: &str) -> (usize, String) {
+        let err = rule.parse::<Rule>().unwrap_err();
+        (err.column, err.message)
+    }
+
+    #[test]
+    fn binds_and_tighter_than_or() {
+        assert_eq!(matches("true || false && false", 0, None), Some(true));
+        assert_eq!(matches("false && false || true", 0, None), Some(true));
+        assert_eq!(matches("(true || false) && false", 0, None), Some(false));
+        assert_eq!(
+            matches("robux >= 100 || id == 1 && robux < 10", 150, None),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn binds_not_tighter_than_and() {
+        assert_eq!(matches("!false && false", 0, None), Some(false));
+        assert_eq!(matches("!(false && false)", 0, None), Some(true));
+        assert_eq!(matches("not true or not false", 0, None), Some(true));
+        assert_eq!(matches("!!true", 0, None), Some(true));
+    }
+
+    #[test]
+    fn compares_numbers() {
+        assert_eq!(matches("robux == 1_000", 1000, None), Some(true));
+        assert_eq!(matches("robux != 5", 5, None), Some(false));
+        assert_eq!(matches("robux < 5", 5, None), Some(false));
+        assert_eq!(matches("robux <= 5", 5, None), Some(true));
+        assert_eq!(matches("id > 6 && id >= 7", 0, None), Some(true));
+    }
+
+    #[test]
+    fn contains_ignores_case() {
+        let info = info();
+        assert_eq!(matches("name contains \"hub\"", 0, Some(&info)), Some(true));
+        assert_eq!(matches("name contains \"HUB\"", 0, Some(&info)), Some(true));
+        assert_eq!(
+            matches("description contains \"rare\"", 0, Some(&info)),
+            Some(false)
+        );
+        assert_eq!(matches("name contains \"\"", 0, Some(&info)), Some(true));
+    }
+
+    #[test]
+    fn empty_rule_matches_everything() {
+        assert_eq!(matches("  ", 0, None), Some(true));
+    }
+
+    #[test]
+    fn waits_for_the_group_info() {
+        assert_eq!(matches("public", 10, None), None);
+        assert_eq!(matches("public && robux > 5", 10, None), None);
+        assert_eq!(matches("!(name contains \"hub\")", 10, None), None);
+        // The known side is enough here
+        assert_eq!(matches("public && robux > 5", 1, None), Some(false));
+        assert_eq!(matches("public || robux > 5", 10, None), Some(true));
+        assert_eq!(
+            matches("public && robux > 5", 10, Some(&info())),
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn reports_unknown_fields() {
+        let (column, message) = error("robux > 5 && members < 3");
+        assert_eq!(column, 14);
+        assert!(message.starts_with("unknown field `members`, expected one of id, robux"));
+    }
+
+    #[test]
+    fn reports_type_mismatches() {
+        assert_eq!(
+            error("name == 5"),
+            (6, "cannot compare text with a number".to_string())
+        );
+        assert_eq!(
+            error("public && robux"),
+            (11, "`&&` needs true or false, not a number".to_string())
+        );
+        assert_eq!(
+            error("robux || public"),
+            (1, "`||` needs true or false, not a number".to_string())
+        );
+        assert_eq!(
+            error("!robux"),
+            (2, "`!` needs true or false, not a number".to_string())
+        );
+        assert_eq!(
+            error("name < \"b\""),
+            (6, "only numbers can be ordered, not text".to_string())
+        );
+        assert_eq!(
+            error("robux contains 5"),
+            (7, "`contains` needs text, not a number".to_string())
+        );
+        assert_eq!(
+            error("robux"),
+            (
+                1,
+                "the rule must be true or false, not a number".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        assert_eq!(
+            error("name contains \"hub"),
+            (15, "the text is missing its closing `\"`".to_string())
+        );
+        assert_eq!(
+            error("(public"),
+            (8, "expected `)`, found the end of the rule".to_string())
+        );
+        assert_eq!(
+            error("public locked"),
+            (8, "expected `&&` or `||`, found `locked`".to_string())
+        );
+        assert_eq!(error("robux > 5 $"), (11, "unexpected `$`".to_string()));
+        assert_eq!(
+            error("public &&"),
+            (
+                10,
+                "expected a field or value, found the end of the rule".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn default_rule_matches_open_unowned_groups() {
+        let rule = Rule::default();
+        assert_eq!(rule.to_string(), DEFAULT_RULE);
+        for &public in &[false, true] {
+            for &locked in &[false, true] {
+                for &owned in &[false, true] {
+                    let mut info = info();
+                    info.public_entry_allowed = public;
+                    info.is_locked = if locked { Some(true) } else { None };
+                    if owned {
+                        info.owner = Some(GroupOwner {
+                            user_id: 1,
+                            username: "bob".to_string(),
+                            display_name: None,
+                        });
+                    }
+                    // What the scanner checked before rules
+                    let expected =
+                        !info.locked() && info.public_entry_allowed && info.owner.is_none();
+                    let group = GroupRecord {
+                        id: 7,
+                        robux: 0,
+                        info: Some(&info),
+                    };
+                    assert_eq!(rule.matches(&group), Some(expected));
+                }
+            }
+        }
+        let group = GroupRecord {
+            id: 7,
+            robux: 0,
+            info: None,
+        };
+        assert_eq!(rule.matches(&group), None);
+    }
+}
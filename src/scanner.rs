@@ -0,0 +1,1302 @@
+use crate::{
+    api::{ApiError, ApiErrorKind, ApiKey, Connector, GroupApi, ReqwestConnector},
+    config::Config,
+    connection::{Connectedness, Connection, Input},
+    governor::TokenBucket,
+    ids::{GroupIds, SweepRange},
+    models::GroupInfoResponse,
+    proxies::ProxyTarget,
+    results::{Checkpoint, GroupSnapshot, Progress, Results, Session},
+    rules::{GroupRecord, RobuxRange, Rule},
+    GroupId,
+};
+use futures::future::join_all;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        watch,
+    },
+    time::{delay_for, Instant},
+};
+
+/// Progress reported by a running scan.
+#[derive(Debug, Clone)]
+pub enum Event {
+    GroupFound {
+        info: GroupInfoResponse,
+        robux: u32,
+    },
+    GroupChecked,
+    ProxyConnected(usize, Connectedness),
+    /// A request failed without ending the proxy's connection.
+    RequestFailed(usize, ApiErrorKind),
+    /// Sent after every request and whenever the proxy connects or disconnects.
+    ProxyStats(usize, ProxyStats),
+}
+
+/// Health of one proxy, kept across its reconnections.
+#[derive(Debug, Clone, Default)]
+pub struct ProxyStats {
+    pub requests: u64,
+    pub successes: u64,
+    /// Responses whose body could not be parsed.
+    pub parse_failures: u64,
+    pub rate_limited: u64,
+    pub last_error: Option<String>,
+    /// Total latency of the `timed` requests.
+    latency: Duration,
+    timed: u64,
+    /// When the current connection was made, `None` while disconnected.
+    connected_since: Option<Instant>,
+}
+
+impl ProxyStats {
+    fn record(&mut self, latency: Option<Duration>, error: Option<&ApiError>) {
+        self.requests += 1;
+        if let Some(latency) = latency {
+            self.latency += latency;
+            self.timed += 1;
+        }
+        match error {
+            None => self.successes += 1,
+            Some(err) => {
+                match err.kind() {
+                    ApiErrorKind::Malformed => self.parse_failures += 1,
+                    ApiErrorKind::RateLimited => self.rate_limited += 1,
+                    _ => {}
+                }
+                self.last_error = Some(err.to_string());
+            }
+        }
+    }
+
+    pub fn average_latency(&self) -> Option<Duration> {
+        if self.timed == 0 {
+            None
+        } else {
+            Some(self.latency / self.timed as u32)
+        }
+    }
+
+    /// How long the current connection has lasted.
+    pub fn uptime(&self) -> Duration {
+        self.connected_since
+            .map(|since| since.elapsed())
+            .unwrap_or_default()
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected_since.is_some()
+    }
+}
+
+fn get_from_watch<T: Clone>(recv: &watch::Receiver<T>) -> T {
+    recv.borrow().clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartError {
+    /// Neither an api key nor a custom connector was given.
+    NoApiKey,
+    /// A session can only be resumed with the results database it was saved in.
+    NoResults,
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StartError::NoApiKey => write!(f, "no api key has been loaded"),
+            StartError::NoResults => write!(f, "the results database is not open"),
+        }
+    }
+}
+
+impl std::error::Error for StartError {}
+
+#[derive(Default)]
+struct Counters {
+    groups_checked: AtomicU64,
+    groups_found: AtomicU64,
+    robux_found: AtomicU64,
+}
+
+impl Counters {
+    fn new(progress: Progress) -> Self {
+        Self {
+            groups_checked: AtomicU64::new(progress.groups_checked),
+            groups_found: AtomicU64::new(progress.groups_found),
+            robux_found: AtomicU64::new(progress.robux_found),
+        }
+    }
+
+    fn progress(&self) -> Progress {
+        Progress {
+            groups_checked: self.groups_checked.load(Ordering::Relaxed),
+            groups_found: self.groups_found.load(Ordering::Relaxed),
+            robux_found: self.robux_found.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Owns the settings shared with scan workers and starts or stops them.
+///
+/// Settings changed while a scan is running are picked up by its workers.
+pub struct Scanner {
+    config: Arc<Config>,
+    proxy_list: Vec<ProxyTarget>,
+    direct_workers: u32,
+    connector: Option<Arc<dyn Connector>>,
+    results: Option<Results>,
+    running_sender: watch::Sender<bool>,
+    running_receiver: watch::Receiver<bool>,
+    premium_groups_sender: watch::Sender<bool>,
+    premium_groups_receiver: watch::Receiver<bool>,
+    robux_range_sender: watch::Sender<RobuxRange>,
+    robux_range_receiver: watch::Receiver<RobuxRange>,
+    rule_sender: watch::Sender<Arc<Rule>>,
+    rule_receiver: watch::Receiver<Arc<Rule>>,
+    requests_per_second_sender: watch::Sender<f64>,
+    requests_per_second_receiver: watch::Receiver<f64>,
+    /// Shared by scans and rechecks, so together they stay under the cap.
+    bucket: Arc<TokenBucket>,
+}
+
+/// The request rate used when `requests_per_second` is 0: unlimited through
+/// proxies, but direct connections must not get the user's address blocked.
+fn default_rate(config: &Config, direct_workers: u32) -> f64 {
+    if direct_workers > 0 {
+        config.direct_requests_per_second
+    } else {
+        0.
+    }
+}
+
+impl Scanner {
+    pub fn new(config: Config, proxy_list: Vec<ProxyTarget>) -> Self {
+        let (running_sender, running_receiver) = watch::channel(false);
+        let (premium_groups_sender, premium_groups_receiver) =
+            watch::channel(config.premium_groups);
+        let (robux_range_sender, robux_range_receiver) = watch::channel(config.robux_range());
+        let (rule_sender, rule_receiver) = watch::channel(Arc::new(config.group_rule()));
+        let (requests_per_second_sender, requests_per_second_receiver) =
+            watch::channel(config.requests_per_second);
+        let bucket = Arc::new(TokenBucket::new(
+            requests_per_second_receiver.clone(),
+            default_rate(&config, config.direct_workers),
+        ));
+        Self {
+            direct_workers: config.direct_workers,
+            config: Arc::new(config),
+            proxy_list,
+            connector: None,
+            results: None,
+            running_sender,
+            running_receiver,
+            premium_groups_sender,
+            premium_groups_receiver,
+            robux_range_sender,
+            robux_range_receiver,
+            rule_sender,
+            rule_receiver,
+            requests_per_second_sender,
+            requests_per_second_receiver,
+            bucket,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn proxy_list(&self) -> &[ProxyTarget] {
+        &self.proxy_list
+    }
+
+    /// Takes effect the next time the scanner is started.
+    pub fn set_proxy_list(&mut self, proxy_list: Vec<ProxyTarget>) {
+        self.proxy_list = proxy_list;
+    }
+
+    pub fn direct_workers(&self) -> u32 {
+        self.direct_workers
+    }
+
+    /// Scans with this many workers connecting without a proxy, or through
+    /// the proxy list if 0. Takes effect the next time the scanner is started.
+    pub fn set_direct_workers(&mut self, direct_workers: u32) {
+        self.direct_workers = direct_workers;
+        self.bucket = Arc::new(TokenBucket::new(
+            self.requests_per_second_receiver.clone(),
+            default_rate(&self.config, direct_workers),
+        ));
+    }
+
+    /// Connects to the configured endpoints with `reqwest`. Takes effect the
+    /// next time the scanner is started.
+    pub fn set_api_key(&mut self, api_key: ApiKey) {
+        self.connector = Some(Arc::new(ReqwestConnector::new(
+            self.config.endpoints(),
+            api_key,
+            self.config.backoff(),
+        )));
+    }
+
+    /// Replaces how workers talk to the API, e.g. to use a mock in tests.
+    /// Takes effect the next time the scanner is started.
+    pub fn set_connector(&mut self, connector: Arc<dyn Connector>) {
+        self.connector = Some(connector);
+    }
+
+    pub fn can_start(&self) -> bool {
+        self.connector.is_some()
+    }
+
+    /// Where found groups are saved. Takes effect the next time the scanner
+    /// is started.
+    pub fn set_results(&mut self, results: Results) {
+        self.results = Some(results);
+    }
+
+    pub fn results(&self) -> Option<&Results> {
+        self.results.as_ref()
+    }
+
+    pub fn set_premium_groups(&self, premium_groups: bool) {
+        self.premium_groups_sender
+            .broadcast(premium_groups)
+            .unwrap();
+    }
+
+    pub fn set_robux_range(&self, robux_range: RobuxRange) {
+        self.robux_range_sender.broadcast(robux_range).unwrap();
+    }
+
+    /// Decides which groups are reported, on top of the robux range and
+    /// the premium setting.
+    pub fn set_rule(&self, rule: Rule) {
+        self.rule_sender.broadcast(Arc::new(rule)).unwrap();
+    }
+
+    pub fn rule(&self) -> Arc<Rule> {
+        get_from_watch(&self.rule_receiver)
+    }
+
+    /// Caps the requests per second of all workers together. 0 removes the cap.
+    pub fn set_requests_per_second(&self, requests_per_second: f64) {
+        self.requests_per_second_sender
+            .broadcast(requests_per_second)
+            .unwrap();
+    }
+
+    pub fn requests_per_second(&self) -> f64 {
+        get_from_watch(&self.requests_per_second_receiver)
+    }
+
+    pub fn is_running(&self) -> bool {
+        get_from_watch(&self.running_receiver)
+    }
+
+    /// Stops any previous scan and prepares a new one. No requests are made
+    /// until [`Scan::events`] is called.
+    pub fn start(&mut self) -> Result<Scan, StartError> {
+        let connector = self.connector.clone().ok_or(StartError::NoApiKey)?;
+        let session = self
+            .results
+            .as_ref()
+            .and_then(|results| match results.start_session() {
+                Ok(session) => Some(session),
+                Err(e) => {
+                    log::error!("event=session_error error=\"{}\"", e);
+                    None
+                }
+            });
+        let ids = GroupIds::new(&self.config);
+        Ok(self.begin(connector, session, ids, Progress::default()))
+    }
+
+    /// Like [`start`](Self::start), but continues a saved session, skipping
+    /// the ids it already checked.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> Result<Scan, StartError> {
+        let connector = self.connector.clone().ok_or(StartError::NoApiKey)?;
+        let results = self.results.as_ref().ok_or(StartError::NoResults)?;
+        let session = results.resume_session(&checkpoint);
+        let ids = GroupIds::restore(checkpoint.ids);
+        Ok(self.begin(connector, Some(session), ids, checkpoint.progress))
+    }
+
+    fn begin(
+        &mut self,
+        connector: Arc<dyn Connector>,
+        session: Option<Session>,
+        ids: GroupIds,
+        progress: Progress,
+    ) -> Scan {
+        self.stop();
+        // Workers of the previous scan keep the old receiver, so they still see it stopped
+        let (running_sender, running_receiver) = watch::channel(true);
+        self.running_sender = running_sender;
+        self.running_receiver = running_receiver;
+        Scan {
+            config: self.config.clone(),
+            ids: Arc::new(ids),
+            counters: Arc::new(Counters::new(progress)),
+            requests_before: self.bucket.granted(),
+            bucket: self.bucket.clone(),
+            session,
+            connections: self.connections(),
+            connector,
+            running: self.running_receiver.clone(),
+            premium_groups: self.premium_groups_receiver.clone(),
+            robux_range: self.robux_range_receiver.clone(),
+            rule: self.rule_receiver.clone(),
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.running_sender.broadcast(false).unwrap();
+    }
+
+    /// The proxy of each worker, `None` for a direct connection.
+    fn connections(&self) -> Vec<Option<ProxyTarget>> {
+        if self.direct_workers > 0 {
+            vec![None; self.direct_workers as usize]
+        } else {
+            self.proxy_list.iter().cloned().map(Some).collect()
+        }
+    }
+
+    /// Checks stored groups again through the scan's connections, saving a
+    /// snapshot of each. Whether a group still qualifies is decided by the
+    /// current robux range, rule and premium setting.
+    pub fn recheck(
+        &self,
+        ids: Vec<GroupId>,
+    ) -> Result<impl Future<Output = Vec<RecheckOutcome>>, StartError> {
+        let connector = self.connector.clone().ok_or(StartError::NoApiKey)?;
+        let results = self.results.clone().ok_or(StartError::NoResults)?;
+        let connections = self.connections();
+        let bucket = self.bucket.clone();
+        let config = self.config.clone();
+        let criteria = Criteria {
+            robux_range: get_from_watch(&self.robux_range_receiver),
+            premium_groups: get_from_watch(&self.premium_groups_receiver),
+            rule: get_from_watch(&self.rule_receiver),
+        };
+        Ok(async move {
+            let queue = Mutex::new(ids.iter().map(|&id| (id, 0)).collect::<VecDeque<_>>());
+            let checked = Mutex::new(Vec::with_capacity(ids.len()));
+            let workers = connections.iter().enumerate().map(|(i, proxy)| {
+                recheck_worker(
+                    i,
+                    proxy.as_ref(),
+                    &*connector,
+                    &bucket,
+                    &config,
+                    &queue,
+                    &checked,
+                )
+            });
+            join_all(workers).await;
+            let mut outcomes = Vec::with_capacity(ids.len());
+            for (id, checked) in checked.into_inner().unwrap() {
+                let outcome = match checked {
+                    Ok((robux, info)) => {
+                        let disqualified = criteria.disqualification(robux, &info);
+                        match results
+                            .record_check(info.clone(), robux, disqualified)
+                            .await
+                        {
+                            Ok(snapshot) => Ok(RecheckedGroup { info, snapshot }),
+                            Err(e) => Err(format!("could not save the result: {}", e)),
+                        }
+                    }
+                    Err(e) => Err(e.to_string()),
+                };
+                match &outcome {
+                    Ok(group) => log::info!(
+                        "event=group_rechecked group={} robux={} qualifies={}",
+                        id,
+                        group.snapshot.robux,
+                        group.snapshot.disqualified.is_none()
+                    ),
+                    Err(e) => log::warn!("event=recheck_failed group={} error=\"{}\"", id, e),
+                }
+                outcomes.push((id, outcome));
+            }
+            // Left over when no connection could be made
+            for (id, _) in queue.into_inner().unwrap() {
+                outcomes.push((id, Err("no proxy could connect".to_string())));
+            }
+            let order: HashMap<_, _> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+            outcomes.sort_by_key(|(id, _)| order[id]);
+            outcomes
+        })
+    }
+}
+
+/// A group passed to [`Scanner::recheck`], and what came of checking it.
+pub type RecheckOutcome = (GroupId, Result<RecheckedGroup, String>);
+
+/// A stored group after [`Scanner::recheck`] fetched it again.
+#[derive(Debug, Clone)]
+pub struct RecheckedGroup {
+    pub info: GroupInfoResponse,
+    pub snapshot: GroupSnapshot,
+}
+
+/// Times a group is put back in the queue after being rate limited.
+const MAX_RECHECK_RETRIES: u32 = 3;
+
+/// What a group must have to be a hit, as of when a recheck started.
+struct Criteria {
+    robux_range: RobuxRange,
+    premium_groups: bool,
+    rule: Arc<Rule>,
+}
+
+impl Criteria {
+    fn disqualification(&self, robux: u32, info: &GroupInfoResponse) -> Option<String> {
+        let record = GroupRecord {
+            id: info.id,
+            robux,
+            info: Some(info),
+        };
+        if !self.robux_range.contains(robux) {
+            Some(format!("{} robux is not {}", robux, self.robux_range))
+        } else if self.rule.matches(&record) != Some(true) {
+            Some(match &info.owner {
+                Some(owner) => format!("does not match the rule, owned by {}", owner.username),
+                None => "does not match the rule".to_string(),
+            })
+        } else if info.is_builders_club_only && !self.premium_groups {
+            Some("only premium members can join".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+/// A group's funds and info as fetched by a recheck worker.
+type Fetched = (GroupId, Result<(u32, GroupInfoResponse), ApiError>);
+
+/// Takes groups off the queue until it is empty, fetching their funds and
+/// info through one connection and waiting `wait_secs` after each, like a
+/// scan does. After `max_connect_attempts` transport errors in a row the
+/// connection is given up on, leaving its groups to the others.
+async fn recheck_worker(
+    i: usize,
+    proxy: Option<&ProxyTarget>,
+    connector: &dyn Connector,
+    bucket: &TokenBucket,
+    config: &Config,
+    queue: &Mutex<VecDeque<(GroupId, u32)>>,
+    checked: &Mutex<Vec<Fetched>>,
+) {
+    let mut failures = 0;
+    let mut connection = None;
+    loop {
+        if failures >= config.max_connect_attempts {
+            log::warn!("event=recheck_retired proxy={} failures={}", i, failures);
+            return;
+        }
+        let api = match connection.take() {
+            Some(api) => api,
+            None if queue.lock().unwrap().is_empty() => return,
+            None => match connector.connect(proxy) {
+                Ok(api) => api,
+                Err(e) => {
+                    log::warn!("event=recheck_connect_failed proxy={} error=\"{}\"", i, e);
+                    failures += 1;
+                    continue;
+                }
+            },
+        };
+        let next = queue.lock().unwrap().pop_front();
+        let (id, retries) = match next {
+            Some(next) => next,
+            None => return,
+        };
+        bucket.acquire().await;
+        let result = match api.fetch_funds(id).await {
+            Ok(funds) => {
+                bucket.acquire().await;
+                api.fetch_group_info(id)
+                    .await
+                    .map(|info| (funds.robux, info))
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Err(e) if e.kind() == ApiErrorKind::Transport => {
+                // Not the group's fault, so it goes back for whichever
+                // connection gets to it first, and this one reconnects
+                log::debug!("event=recheck_transport_error proxy={} error=\"{}\"", i, e);
+                queue.lock().unwrap().push_front((id, retries));
+                failures += 1;
+                continue;
+            }
+            Err(e) if e.kind() == ApiErrorKind::RateLimited && retries < MAX_RECHECK_RETRIES => {
+                // The connection's governor waits before the next request
+                queue.lock().unwrap().push_back((id, retries + 1));
+            }
+            result => {
+                failures = 0;
+                checked.lock().unwrap().push((id, result));
+                delay_for(config.wait_time()).await;
+            }
+        }
+        connection = Some(api);
+    }
+}
+
+/// A single run of a [`Scanner`].
+#[derive(Clone)]
+pub struct Scan {
+    config: Arc<Config>,
+    ids: Arc<GroupIds>,
+    counters: Arc<Counters>,
+    bucket: Arc<TokenBucket>,
+    /// Requests the scanner had sent before this run.
+    requests_before: u64,
+    session: Option<Session>,
+    /// The proxy of each worker, `None` for a direct connection.
+    connections: Vec<Option<ProxyTarget>>,
+    connector: Arc<dyn Connector>,
+    running: watch::Receiver<bool>,
+    premium_groups: watch::Receiver<bool>,
+    robux_range: watch::Receiver<RobuxRange>,
+    rule: watch::Receiver<Arc<Rule>>,
+}
+
+impl Scan {
+    pub fn connections(&self) -> &[Option<ProxyTarget>] {
+        &self.connections
+    }
+
+    /// The range being swept, unless group ids are picked at random.
+    pub fn sweep_range(&self) -> Option<SweepRange> {
+        self.ids.sweep_range()
+    }
+
+    /// Percentage of the sweep range checked so far.
+    pub fn coverage(&self) -> Option<f64> {
+        self.ids.coverage()
+    }
+
+    /// Totals of the session, including the runs it was resumed from.
+    pub fn progress(&self) -> Progress {
+        self.counters.progress()
+    }
+
+    /// The cap on requests per second of all workers together, 0 if there is none.
+    pub fn rate_limit(&self) -> f64 {
+        self.bucket.rate()
+    }
+
+    /// Requests sent by this run, for measuring its request rate.
+    pub fn requests_sent(&self) -> u64 {
+        self.bucket.granted() - self.requests_before
+    }
+
+    /// Saves the scan's progress to the results database, if it has one.
+    pub async fn checkpoint(&self) {
+        if let Some(session) = &self.session {
+            let finished = self.ids.is_finished();
+            let snapshot = self.ids.snapshot();
+            if let Err(e) = session
+                .save_checkpoint(snapshot.clone(), self.progress(), finished)
+                .await
+            {
+                log::error!("event=checkpoint_error error=\"{}\"", e);
+                self.ids.unsaved(snapshot);
+            }
+        }
+    }
+
+    /// Spawns one worker per connection and returns the stream of their events.
+    /// The stream ends once every worker has disconnected.
+    pub fn events(self) -> UnboundedReceiver<Event> {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        let mut workers = Vec::new();
+
+        for (i, proxy) in self.connections.iter().cloned().enumerate() {
+            workers.push(tokio::spawn(
+                Worker {
+                    index: i,
+                    proxy,
+                    connector: self.connector.clone(),
+                    ids: self.ids.clone(),
+                    counters: self.counters.clone(),
+                    bucket: self.bucket.clone(),
+                    session: self.session.clone(),
+                    config: self.config.clone(),
+                    running: self.running.clone(),
+                    premium_groups: self.premium_groups.clone(),
+                    robux_range: self.robux_range.clone(),
+                    rule: self.rule.clone(),
+                    txc: tx.clone(),
+                    connection: Connection::new(self.config.connection_policy()),
+                    stats: ProxyStats::default(),
+                    pending_id: None,
+                }
+                .run(),
+            ));
+        }
+
+        if self.session.is_some() {
+            tokio::spawn(async move {
+                let interval = self.config.checkpoint_time();
+                let mut checkpoints = tokio::time::interval_at(Instant::now() + interval, interval);
+                let workers = futures::future::join_all(workers);
+                tokio::pin!(workers);
+                loop {
+                    tokio::select! {
+                        _ = &mut workers => break,
+                        _ = checkpoints.tick() => self.checkpoint().await,
+                    }
+                }
+                self.checkpoint().await;
+            });
+        }
+
+        rx
+    }
+}
+
+/// Checks groups through one proxy, driving its [`Connection`] until it is retired.
+struct Worker {
+    index: usize,
+    proxy: Option<ProxyTarget>,
+    connector: Arc<dyn Connector>,
+    ids: Arc<GroupIds>,
+    counters: Arc<Counters>,
+    bucket: Arc<TokenBucket>,
+    session: Option<Session>,
+    config: Arc<Config>,
+    running: watch::Receiver<bool>,
+    premium_groups: watch::Receiver<bool>,
+    robux_range: watch::Receiver<RobuxRange>,
+    rule: watch::Receiver<Arc<Rule>>,
+    txc: UnboundedSender<Event>,
+    connection: Connection,
+    stats: ProxyStats,
+    /// Kept while cooling down so a rate limited request is retried with the same id
+    pending_id: Option<GroupId>,
+}
+
+impl Worker {
+    async fn run(mut self) {
+        let i = self.index;
+        let mut api: Option<Box<dyn GroupApi>> = None;
+        let mut reported = None;
+        loop {
+            if !get_from_watch(&self.running) {
+                self.connection.handle(Input::Stopped);
+            }
+            let state = self.connection.state();
+            if reported != Some(state) {
+                self.state_changed(state);
+                reported = Some(state);
+            }
+            match state {
+                Connectedness::Retired => break,
+                Connectedness::CoolingDown => {
+                    delay_for(self.connection.cooldown()).await;
+                    self.connection.handle(Input::CooledDown);
+                    continue;
+                }
+                Connectedness::Backoff => {
+                    api = None;
+                    let wait = self.config.backoff().delay(self.connection.failures());
+                    log::debug!(
+                        "event=backoff proxy={} attempt={} wait={:.1}s",
+                        i,
+                        self.connection.failures(),
+                        wait.as_secs_f32()
+                    );
+                    delay_for(wait).await;
+                    self.connection.handle(Input::BackedOff);
+                    continue;
+                }
+                Connectedness::Connecting | Connectedness::Active => {}
+            }
+            if api.is_none() {
+                match self.connector.connect(self.proxy.as_ref()) {
+                    Ok(new_api) => api = Some(new_api),
+                    Err(err) => {
+                        self.connection_failed(err);
+                        continue;
+                    }
+                }
+            }
+            let group_id = match self.pending_id.or_else(|| self.ids.next()) {
+                Some(id) => id,
+                None => {
+                    log::info!("event=ids_exhausted proxy={}", i);
+                    self.connection.handle(Input::Stopped);
+                    continue;
+                }
+            };
+            self.pending_id = Some(group_id);
+            match self.check_group(api.as_deref().unwrap(), group_id).await {
+                Ok(robux) => {
+                    self.pending_id = None;
+                    self.ids.mark_checked(group_id);
+                    self.counters.groups_checked.fetch_add(1, Ordering::Relaxed);
+                    self.connection.handle(Input::Checked);
+                    log::debug!(
+                        "event=group_checked proxy={} group={} robux={}",
+                        i,
+                        group_id,
+                        robux
+                    );
+                    self.txc.send(Event::GroupChecked).ok();
+                    delay_for(self.config.wait_time()).await;
+                }
+                Err(err @ ApiError::Transport(_)) => self.connection_failed(err),
+                Err(err) => {
+                    log::debug!(
+                        "event=request_failed proxy={} group={} error={:?}",
+                        i,
+                        group_id,
+                        err
+                    );
+                    self.txc.send(Event::RequestFailed(i, err.kind())).ok();
+                    match err {
+                        ApiError::RateLimited { retry_after } => {
+                            let wait = retry_after.unwrap_or_else(|| self.config.cooldown_time());
+                            log::info!(
+                                "event=rate_limited proxy={} wait={:.1}s",
+                                i,
+                                wait.as_secs_f32()
+                            );
+                            self.connection.handle(Input::RateLimited(wait));
+                        }
+                        ApiError::NotFound => {
+                            // Asking again would fail the same way
+                            self.pending_id = None;
+                            self.ids.mark_checked(group_id);
+                            delay_for(self.config.wait_time()).await;
+                        }
+                        ApiError::Unauthorized => {
+                            // Every other request would be refused too, e.g. with a bad api key
+                            log::warn!("event=unauthorized proxy={} group={}", i, group_id);
+                            self.pending_id = None;
+                            self.ids.give_back(group_id);
+                            self.connection.handle(Input::Stopped);
+                        }
+                        _ => {
+                            // The group was not looked at, so it is left unfinished
+                            self.pending_id = None;
+                            self.ids.give_back(group_id);
+                            self.connection.handle(Input::Failed);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(id) = self.pending_id {
+            self.ids.give_back(id);
+        }
+    }
+
+    fn state_changed(&mut self, state: Connectedness) {
+        let i = self.index;
+        match state {
+            Connectedness::Active if self.stats.connected_since.is_none() => {
+                self.stats.connected_since = Some(Instant::now());
+                log::info!("event=connected proxy={}", i);
+            }
+            Connectedness::Backoff | Connectedness::Retired
+                if self.stats.connected_since.take().is_some() =>
+            {
+                log::info!("event=disconnected proxy={}", i);
+            }
+            _ => {}
+        }
+        if state == Connectedness::Retired {
+            log::info!("event=retired proxy={}", i);
+        }
+        self.txc.send(Event::ProxyConnected(i, state)).ok();
+        self.txc.send(Event::ProxyStats(i, self.stats.clone())).ok();
+    }
+
+    fn connection_failed(&mut self, err: ApiError) {
+        let connect_error = match &err {
+            ApiError::Transport(e) => matches!(
+                e.source().and_then(|s| s.downcast_ref::<hyper::Error>()),
+                Some(e) if e.is_connect()
+            ),
+            _ => false,
+        };
+        if connect_error {
+            log::debug!("event=connect_failed proxy={} error={:?}", self.index, err);
+        } else {
+            log::warn!(
+                "event=connection_error proxy={} attempt={} error={:?}",
+                self.index,
+                self.connection.failures() + 1,
+                err
+            );
+        }
+        self.stats.last_error = Some(err.to_string());
+        // Another worker can check it while this one reconnects
+        if let Some(id) = self.pending_id.take() {
+            self.ids.give_back(id);
+        }
+        self.connection.handle(Input::Failed);
+    }
+
+    /// Records a response in the proxy's stats. The first one that got
+    /// through the proxy means it is connected.
+    fn record<T>(&mut self, api: &dyn GroupApi, response: &Result<T, ApiError>) {
+        if !matches!(response, Err(ApiError::Transport(_))) {
+            self.connection.handle(Input::Connected);
+        }
+        self.stats
+            .record(api.last_latency(), response.as_ref().err());
+        self.txc
+            .send(Event::ProxyStats(self.index, self.stats.clone()))
+            .ok();
+    }
+
+    /// Checks one group, reporting it if it is found. Returns its robux.
+    async fn check_group(
+        &mut self,
+        api: &dyn GroupApi,
+        group_id: GroupId,
+    ) -> Result<u32, ApiError> {
+        let i = self.index;
+        self.bucket.acquire().await;
+        let funds = api.fetch_funds(group_id).await;
+        self.record(api, &funds);
+        let funds = funds?;
+        let rule = get_from_watch(&self.rule);
+        let mut record = GroupRecord {
+            id: group_id,
+            robux: funds.robux,
+            info: None,
+        };
+        if !get_from_watch(&self.robux_range).contains(funds.robux)
+            || rule.matches(&record) == Some(false)
+        {
+            return Ok(funds.robux);
+        }
+        self.bucket.acquire().await;
+        let owner = api.fetch_group_info(group_id).await;
+        self.record(api, &owner);
+        let owner = owner?;
+        record.info = Some(&owner);
+        let premium = owner.is_builders_club_only;
+        let accepting_premium_groups = get_from_watch(&self.premium_groups);
+        if (!premium || accepting_premium_groups) && rule.matches(&record) == Some(true) {
+            log::info!(
+                "event=group_found proxy={} group={} robux={}",
+                i,
+                group_id,
+                funds.robux
+            );
+            self.counters.groups_found.fetch_add(1, Ordering::Relaxed);
+            self.counters
+                .robux_found
+                .fetch_add(funds.robux as u64, Ordering::Relaxed);
+            self.txc
+                .send(Event::GroupFound {
+                    info: owner.clone(),
+                    robux: funds.robux,
+                })
+                .ok();
+            if let Some(session) = &self.session {
+                if let Err(e) = session.record_group(funds.robux, owner).await {
+                    log::error!(
+                        "event=results_error proxy={} group={} error=\"{}\"",
+                        i,
+                        group_id,
+                        e
+                    );
+                }
+            }
+        }
+        Ok(funds.robux)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Strategy,
+        models::{FundsResponse, GroupOwner},
+    };
+    use async_trait::async_trait;
+    use serde_json as json;
+
+    /// Canned answers shared by every connection of a [`StubConnector`].
+    /// Groups without canned funds or info do not exist.
+    #[derive(Default)]
+    struct Stub {
+        funds: HashMap<GroupId, u32>,
+        info: HashMap<GroupId, GroupInfoResponse>,
+        /// Answered to funds requests for a group, in order, before its funds.
+        errors: Mutex<HashMap<GroupId, VecDeque<ApiError>>>,
+        /// Proxies none of whose requests get through.
+        dead: Vec<String>,
+        /// Every request sent, as the proxy, what was asked for and the group.
+        requests: Mutex<Vec<(Option<String>, &'static str, GroupId)>>,
+    }
+
+    impl Stub {
+        fn requests(&self, kind: &str) -> Vec<GroupId> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .iter()
+                .filter(|(_, k, _)| *k == kind)
+                .map(|&(_, _, id)| id)
+                .collect()
+        }
+    }
+
+    struct StubConnector(Arc<Stub>);
+
+    impl Connector for StubConnector {
+        fn connect(&self, proxy: Option<&ProxyTarget>) -> Result<Box<dyn GroupApi>, ApiError> {
+            Ok(Box::new(StubApi {
+                stub: self.0.clone(),
+                proxy: proxy.map(|proxy| proxy.url.clone()),
+            }))
+        }
+    }
+
+    struct StubApi {
+        stub: Arc<Stub>,
+        proxy: Option<String>,
+    }
+
+    impl StubApi {
+        fn send(&self, kind: &'static str, id: GroupId) -> Result<(), ApiError> {
+            let mut requests = self.stub.requests.lock().unwrap();
+            requests.push((self.proxy.clone(), kind, id));
+            match &self.proxy {
+                Some(proxy) if self.stub.dead.contains(proxy) => {
+                    Err(ApiError::Transport("connection reset".into()))
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl GroupApi for StubApi {
+        async fn fetch_funds(&self, id: GroupId) -> Result<FundsResponse, ApiError> {
+            self.send("funds", id)?;
+            let error = self
+                .stub
+                .errors
+                .lock()
+                .unwrap()
+                .get_mut(&id)
+                .and_then(VecDeque::pop_front);
+            if let Some(error) = error {
+                return Err(error);
+            }
+            match self.stub.funds.get(&id) {
+                Some(&robux) => Ok(FundsResponse { robux }),
+                None => Err(ApiError::NotFound),
+            }
+        }
+
+        async fn fetch_group_info(&self, id: GroupId) -> Result<GroupInfoResponse, ApiError> {
+            self.send("info", id)?;
+            self.stub.info.get(&id).cloned().ok_or(ApiError::NotFound)
+        }
+    }
+
+    /// An open group nobody owns.
+    fn group(id: GroupId) -> GroupInfoResponse {
+        GroupInfoResponse {
+            id,
+            name: format!("Group {}", id),
+            description: None,
+            owner: None,
+            shout: None,
+            member_count: Some(1),
+            is_builders_club_only: false,
+            public_entry_allowed: true,
+            is_locked: None,
+        }
+    }
+
+    /// Sweeps groups 1 to 6 without waiting between requests.
+    fn config() -> Config {
+        Config {
+            strategy: Strategy::Sweep,
+            sweep_start: 1,
+            sweep_end: 7,
+            wait_secs: 0,
+            backoff_secs: 0,
+            cooldown_secs: 0,
+            max_connect_attempts: 2,
+            ..Config::default()
+        }
+    }
+
+    fn json_error() -> json::Error {
+        json::from_str::<FundsResponse>("{}").unwrap_err()
+    }
+
+    fn scanner(stub: &Arc<Stub>, proxies: &[&str]) -> Scanner {
+        scanner_with(config(), stub, proxies)
+    }
+
+    fn scanner_with(config: Config, stub: &Arc<Stub>, proxies: &[&str]) -> Scanner {
+        let proxies = proxies
+            .iter()
+            .map(|url| ProxyTarget {
+                url: url.to_string(),
+                credentials: None,
+            })
+            .collect();
+        let mut scanner = Scanner::new(config, proxies);
+        scanner.set_connector(Arc::new(StubConnector(stub.clone())));
+        scanner
+    }
+
+    async fn run(scan: Scan) -> Vec<Event> {
+        let mut events = scan.events();
+        let mut all = Vec::new();
+        while let Some(event) = events.recv().await {
+            all.push(event);
+        }
+        all
+    }
+
+    fn found(events: &[Event]) -> Vec<(GroupId, u32)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::GroupFound { info, robux } => Some((info.id, *robux)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn states(events: &[Event]) -> Vec<Connectedness> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                Event::ProxyConnected(_, state) => Some(*state),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn reports_groups_that_match() {
+        let mut owned = group(3);
+        owned.owner = Some(GroupOwner {
+            user_id: 1,
+            username: "bob".to_string(),
+            display_name: None,
+        });
+        let mut locked = group(4);
+        locked.is_locked = Some(true);
+        let mut premium = group(5);
+        premium.is_builders_club_only = true;
+        let stub = Arc::new(Stub {
+            funds: vec![(1, 0), (2, 50), (3, 50), (4, 50), (5, 50)]
+                .into_iter()
+                .collect(),
+            info: vec![(2, group(2)), (3, owned), (4, locked), (5, premium)]
+                .into_iter()
+                .collect(),
+            ..Stub::default()
+        });
+        let mut scanner = scanner(&stub, &["socks5://a:1080"]);
+        let scan = scanner.start().unwrap();
+        let events = run(scan.clone()).await;
+
+        assert_eq!(found(&events), vec![(2, 50)]);
+        // Group 1 has no robux, so its info is never needed
+        assert_eq!(stub.requests("info"), vec![2, 3, 4, 5]);
+        let progress = scan.progress();
+        // Group 6 does not exist, which is not a check
+        assert_eq!(progress.groups_checked, 5);
+        assert_eq!(progress.groups_found, 1);
+        assert_eq!(progress.robux_found, 50);
+        assert_eq!(scan.coverage(), Some(100.));
+        assert_eq!(states(&events).last(), Some(&Connectedness::Retired));
+    }
+
+    #[tokio::test]
+    async fn retries_rate_limited_groups() {
+        let mut errors = HashMap::new();
+        errors.insert(
+            2,
+            vec![ApiError::RateLimited {
+                retry_after: Some(Duration::from_millis(1)),
+            }]
+            .into_iter()
+            .collect(),
+        );
+        let stub = Arc::new(Stub {
+            funds: (1..7).map(|id| (id, 0)).chain(Some((2, 50))).collect(),
+            info: vec![(2, group(2))].into_iter().collect(),
+            errors: Mutex::new(errors),
+            ..Stub::default()
+        });
+        let mut scanner = scanner(&stub, &["socks5://a:1080"]);
+        let scan = scanner.start().unwrap();
+        let events = run(scan.clone()).await;
+
+        assert_eq!(found(&events), vec![(2, 50)]);
+        assert_eq!(stub.requests("funds"), vec![1, 2, 2, 3, 4, 5, 6]);
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, Event::RequestFailed(0, ApiErrorKind::RateLimited))));
+        assert!(states(&events).contains(&Connectedness::CoolingDown));
+        assert_eq!(scan.progress().groups_checked, 6);
+    }
+
+    #[tokio::test]
+    async fn retires_connections_that_keep_failing() {
+        let stub = Arc::new(Stub {
+            funds: (1..7).map(|id| (id, 50)).collect(),
+            dead: vec!["socks5://a:1080".to_string()],
+            ..Stub::default()
+        });
+        let mut scanner = scanner(&stub, &["socks5://a:1080"]);
+        let scan = scanner.start().unwrap();
+        let events = run(scan.clone()).await;
+
+        // One request per attempt, always for the group it was given back
+        assert_eq!(stub.requests("funds"), vec![1; 2]);
+        assert_eq!(
+            states(&events),
+            vec![
+                Connectedness::Connecting,
+                Connectedness::Backoff,
+                Connectedness::Connecting,
+                Connectedness::Retired
+            ]
+        );
+        assert_eq!(scan.progress().groups_checked, 0);
+        // Left for whichever connection comes next
+        assert_eq!(scan.ids.next(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn rechecks_around_dead_connections() {
+        let stub = Arc::new(Stub {
+            funds: (1..4).map(|id| (id, 50)).collect(),
+            info: (1..4).map(|id| (id, group(id))).collect(),
+            dead: vec!["socks5://dead:1080".to_string()],
+            ..Stub::default()
+        });
+        let mut scanner = scanner(&stub, &["socks5://dead:1080", "socks5://a:1080"]);
+        let results = Results::open_in_memory().unwrap();
+        let session = results.start_session().unwrap();
+        for id in 1..4 {
+            session.record_group(50, group(id)).await.unwrap();
+        }
+        scanner.set_results(results);
+        let outcomes = scanner.recheck(vec![3, 1, 2]).unwrap().await;
+
+        let ids: Vec<_> = outcomes.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![3, 1, 2]);
+        for (_, outcome) in &outcomes {
+            let group = outcome.as_ref().unwrap();
+            assert_eq!(group.snapshot.robux, 50);
+            assert_eq!(group.snapshot.disqualified, None);
+        }
+        let dead = stub
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(proxy, ..)| proxy.as_deref() == Some("socks5://dead:1080"))
+            .count();
+        assert_eq!(dead, config().max_connect_attempts as usize);
+    }
+
+    #[tokio::test]
+    async fn rechecks_fail_without_a_connection() {
+        let stub = Arc::new(Stub {
+            dead: vec!["socks5://dead:1080".to_string()],
+            ..Stub::default()
+        });
+        let mut scanner = scanner(&stub, &["socks5://dead:1080"]);
+        scanner.set_results(Results::open_in_memory().unwrap());
+        let outcomes = scanner.recheck(vec![1, 2]).unwrap().await;
+
+        assert_eq!(outcomes.len(), 2);
+        for (_, outcome) in outcomes {
+            assert_eq!(outcome.unwrap_err(), "no proxy could connect");
+        }
+    }
+
+    #[tokio::test]
+    async fn leaves_groups_unfinished_when_the_server_fails() {
+        let mut errors = HashMap::new();
+        errors.insert(
+            2,
+            vec![
+                ApiError::Status(503, None),
+                ApiError::Malformed(json_error()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        let stub = Arc::new(Stub {
+            funds: (1..7).map(|id| (id, 0)).collect(),
+            errors: Mutex::new(errors),
+            ..Stub::default()
+        });
+        let mut config = config();
+        config.max_connect_attempts = 3;
+        let mut scanner = scanner_with(config, &stub, &["socks5://a:1080"]);
+        let scan = scanner.start().unwrap();
+        let events = run(scan.clone()).await;
+
+        // Backed off twice, then checked the group it had given back
+        assert_eq!(stub.requests("funds"), vec![1, 2, 2, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            states(&events)
+                .iter()
+                .filter(|&&state| state == Connectedness::Backoff)
+                .count(),
+            2
+        );
+        assert_eq!(scan.progress().groups_checked, 6);
+        assert_eq!(scan.coverage(), Some(100.));
+    }
+
+    #[tokio::test]
+    async fn retires_connections_that_are_refused() {
+        let mut errors = HashMap::new();
+        errors.insert(1, vec![ApiError::Unauthorized].into_iter().collect());
+        let stub = Arc::new(Stub {
+            funds: (1..7).map(|id| (id, 0)).collect(),
+            errors: Mutex::new(errors),
+            ..Stub::default()
+        });
+        let mut scanner = scanner(&stub, &["socks5://a:1080"]);
+        let scan = scanner.start().unwrap();
+        let events = run(scan.clone()).await;
+
+        assert_eq!(stub.requests("funds"), vec![1]);
+        assert_eq!(states(&events).last(), Some(&Connectedness::Retired));
+        assert_eq!(scan.progress().groups_checked, 0);
+        assert_eq!(scan.coverage(), Some(0.));
+        assert_eq!(scan.ids.next(), Some(1));
+    }
+}
@@ -1,12 +1,26 @@
-use crate::GroupId;
+use crate::proxy_editor::{EditorMsg, ProxyEditor};
+use groupfinder::{
+    api::{ApiErrorKind, ApiKey},
+    config::Config,
+    connection::Connectedness,
+    logging::{self, Entry},
+    models::GroupInfoResponse,
+    proxies::{generate_proxies_list, get_proxies_list, LoadError, ProxyList},
+    results::{format_unix_time, unix_time, Checkpoint, GroupSnapshot, Results, StoredGroup},
+    rules::{RobuxRange, Rule},
+    scanner::{ProxyStats, RecheckOutcome, Scan, Scanner, StartError},
+    GroupId,
+};
 use iced::{
     widget, Application, Color, Command, Element, HorizontalAlignment, Length, Subscription,
     VerticalAlignment,
 };
-use serde_json as json;
-use std::{collections::BTreeMap, time::Instant};
+use log::Level;
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
 
-const PROXIES_LOC: &str = "proxies.json";
 const PREMIUM499: Premium = Premium {
     robux_per_month: 450,
     price: "$4.99",
@@ -32,50 +46,198 @@ impl Premium {
     }
 }
 
-pub async fn get_proxies_list() -> Result<Vec<String>, std::io::ErrorKind> {
-    let bytes = tokio::fs::read(PROXIES_LOC).await.map_err(|e| e.kind())?;
-    Ok(json::from_slice(&bytes).map_err(|e| Into::<std::io::Error>::into(e).kind())?)
-}
-
-pub async fn generate_proxies_list() -> Result<Vec<String>, ()> {
-    const PROXIES_LIST_URL: &str = "https://api.proxyscrape.com/?request=getproxies&proxytype=socks5&timeout=10000&country=all";
-    let list = reqwest::get(PROXIES_LIST_URL)
-        .await
-        .map_err(drop)?
-        .text()
-        .await
-        .map_err(drop)?
-        .trim()
-        .split("\r\n")
-        .map(|s| format!("socks5://{}", s))
-        .collect();
-    tokio::fs::write(PROXIES_LOC, json::to_string(&list).unwrap())
-        .await
-        .ok();
-    Ok(list)
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum Connectedness {
-    Connected,
-    Unconnected,
-    RateLimited,
-}
-
 #[derive(Debug, Clone)]
 pub enum Msg {
-    ProxyListLoaded(Result<Vec<String>, std::io::ErrorKind>),
+    ProxyListLoaded(Result<ProxyList, LoadError>),
     GenerateProxies,
-    GroupFound {
-        group: (Option<String>, GroupId),
-        robux: u32,
-    },
+    ToggleProxyEditor,
+    ProxyEditor(EditorMsg),
+    GroupFound { info: GroupInfoResponse, robux: u32 },
     ToggleRunning,
+    ResumeSession,
+    /// Every worker of the scan has disconnected.
+    ScanEnded,
+    CheckpointLoaded(Result<Option<Checkpoint>, String>),
     ProxyConnected(usize, Connectedness),
     SetPremiumGroups(bool),
     UpdateMinimumRobux(String),
+    UpdateMaximumRobux(String),
+    UpdateRequestsPerSecond(String),
+    UpdateRule(String),
     OpenGroup(GroupId),
+    ToggleGroupDetails(GroupId),
+    RecheckGroup(GroupId),
+    RecheckAll,
+    Rechecked(Vec<RecheckOutcome>),
+    GroupsLoaded(Result<Vec<StoredGroup>, String>),
+    VisitedSaved(Result<(), String>),
     GroupChecked,
+    RequestFailed(usize, ApiErrorKind),
+    ApiKeyInputChanged(String),
+    SaveApiKey,
+    ApiKeySaved(Result<ApiKey, String>),
+    LogEntry(Entry),
+    ToggleLogs,
+    SetLogLevel(Level),
+    ProxyStats(usize, ProxyStats),
+    SelectProxy(usize),
+    SetDirect(bool),
+    SortProxyStats(StatsColumn),
+    SortGroups(GroupColumn),
+    SearchGroups(String),
+    FilterVisited(GroupFilter),
+    FilterPremium(GroupFilter),
+}
+
+/// Columns of the proxy detail table, which can be sorted by any of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsColumn {
+    Proxy,
+    Requests,
+    Successes,
+    ParseFailures,
+    RateLimited,
+    Latency,
+    Uptime,
+}
+
+impl StatsColumn {
+    const ALL: [StatsColumn; 7] = [
+        StatsColumn::Proxy,
+        StatsColumn::Requests,
+        StatsColumn::Successes,
+        StatsColumn::ParseFailures,
+        StatsColumn::RateLimited,
+        StatsColumn::Latency,
+        StatsColumn::Uptime,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            StatsColumn::Proxy => "Proxy",
+            StatsColumn::Requests => "Requests",
+            StatsColumn::Successes => "Succeeded",
+            StatsColumn::ParseFailures => "Malformed",
+            StatsColumn::RateLimited => "Rate limited",
+            StatsColumn::Latency => "Latency",
+            StatsColumn::Uptime => "Uptime",
+        }
+    }
+
+    fn key(self, index: usize, stats: &ProxyStats) -> u128 {
+        match self {
+            StatsColumn::Proxy => index as u128,
+            StatsColumn::Requests => stats.requests as u128,
+            StatsColumn::Successes => stats.successes as u128,
+            StatsColumn::ParseFailures => stats.parse_failures as u128,
+            StatsColumn::RateLimited => stats.rate_limited as u128,
+            StatsColumn::Latency => stats
+                .average_latency()
+                .map(|latency| latency.as_millis())
+                .unwrap_or(0),
+            StatsColumn::Uptime => stats.uptime().as_millis(),
+        }
+    }
+
+    fn cell(self, index: usize, stats: &ProxyStats) -> String {
+        match self {
+            StatsColumn::Proxy => index.to_string(),
+            StatsColumn::Latency => match stats.average_latency() {
+                Some(latency) => format!("{} ms", latency.as_millis()),
+                None => "-".to_string(),
+            },
+            StatsColumn::Uptime if !stats.is_connected() => "-".to_string(),
+            StatsColumn::Uptime => format_duration(stats.uptime()),
+            _ => self.key(index, stats).to_string(),
+        }
+    }
+}
+
+/// Columns the found groups can be sorted by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupColumn {
+    Robux,
+    Name,
+    Id,
+    Found,
+}
+
+impl GroupColumn {
+    const ALL: [GroupColumn; 4] = [
+        GroupColumn::Robux,
+        GroupColumn::Name,
+        GroupColumn::Id,
+        GroupColumn::Found,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            GroupColumn::Robux => "Robux",
+            GroupColumn::Name => "Name",
+            GroupColumn::Id => "Id",
+            GroupColumn::Found => "Found",
+        }
+    }
+
+    /// Ascending order, ties broken by group id.
+    fn compare(self, a: &GroupInfo, b: &GroupInfo) -> std::cmp::Ordering {
+        let by_column = match self {
+            GroupColumn::Robux => a.robux.cmp(&b.robux),
+            GroupColumn::Name => {
+                let name = |gi: &GroupInfo| gi.name.as_deref().unwrap_or("").to_lowercase();
+                name(a).cmp(&name(b))
+            }
+            GroupColumn::Id => a.id.cmp(&b.id),
+            GroupColumn::Found => a.first_seen.cmp(&b.first_seen),
+        };
+        by_column.then(a.id.cmp(&b.id))
+    }
+}
+
+/// Shows all found groups, or only those with or without a property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupFilter {
+    All,
+    With,
+    Without,
+}
+
+impl GroupFilter {
+    fn allows(self, value: bool) -> bool {
+        match self {
+            GroupFilter::All => true,
+            GroupFilter::With => value,
+            GroupFilter::Without => !value,
+        }
+    }
+}
+
+/// The search and filters of the found list.
+struct GroupQuery {
+    /// Lowercase, matched against the name and id.
+    search: String,
+    visited: GroupFilter,
+    premium: GroupFilter,
+}
+
+impl GroupQuery {
+    fn matches(&self, gi: &GroupInfo) -> bool {
+        let found = self.search.is_empty()
+            || gi.id.to_string().contains(&self.search)
+            || matches!(&gi.name, Some(name) if name.to_lowercase().contains(&self.search));
+        found && self.visited.allows(gi.visited) && self.premium.allows(gi.premium)
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
 }
 
 pub struct GroupInfo {
@@ -84,31 +246,329 @@ pub struct GroupInfo {
     robux: u32,
     state: widget::button::State,
     visited: bool,
+    /// Found by this run, rather than only loaded from the results database.
+    found: bool,
+    premium: bool,
+    /// When the group was first found, seconds since the Unix epoch.
+    first_seen: i64,
+    /// The full group info, missing for groups stored by older versions.
+    details: Option<GroupInfoResponse>,
+    /// Every time the group was found or checked again, oldest first.
+    history: Vec<GroupSnapshot>,
+    expanded: bool,
+    expand_state: widget::button::State,
+    rechecking: bool,
+    recheck_error: Option<String>,
+    recheck_state: widget::button::State,
 }
 
 impl GroupInfo {
-    fn view(&mut self) -> Element<Msg> {
-        widget::Button::new(
-            &mut self.state,
-            widget::Text::new(format!(
-                "Group \"{}\": {} robux",
-                self.name
-                    .as_ref()
-                    .map(|s| &s[..])
-                    .unwrap_or("(unknown group name)"),
-                self.robux
-            )),
+    fn found(info: GroupInfoResponse, robux: u32) -> Self {
+        Self {
+            name: Some(info.name.clone()),
+            id: info.id,
+            robux,
+            state: Default::default(),
+            visited: false,
+            found: true,
+            premium: info.is_builders_club_only,
+            first_seen: unix_time(),
+            history: vec![found_snapshot(&info, robux)],
+            details: Some(info),
+            expanded: false,
+            expand_state: Default::default(),
+            rechecking: false,
+            recheck_error: None,
+            recheck_state: Default::default(),
+        }
+    }
+
+    fn stored(group: StoredGroup) -> Self {
+        Self {
+            name: group.name,
+            id: group.id,
+            robux: group.robux,
+            state: Default::default(),
+            visited: group.visited,
+            found: false,
+            premium: group.premium,
+            first_seen: group.first_seen,
+            details: group.details,
+            history: group.history,
+            expanded: false,
+            expand_state: Default::default(),
+            rechecking: false,
+            recheck_error: None,
+            recheck_state: Default::default(),
+        }
+    }
+
+    fn disqualified(&self) -> Option<&str> {
+        self.history.last()?.disqualified.as_deref()
+    }
+
+    /// How the robux changed since the check before the last one.
+    fn robux_delta(&self) -> Option<i64> {
+        match self.history.as_slice() {
+            [.., before, last] if before.robux != last.robux => {
+                Some(last.robux as i64 - before.robux as i64)
+            }
+            _ => None,
+        }
+    }
+
+    /// `can_recheck` is false while a scan is running.
+    fn view(&mut self, can_recheck: bool) -> Element<Msg> {
+        let status = if self.rechecking {
+            " - checking again".to_string()
+        } else if self.recheck_error.is_some() {
+            " - check failed".to_string()
+        } else if let Some(reason) = self.disqualified() {
+            format!(" - no longer qualifies: {}", reason)
+        } else {
+            String::new()
+        };
+        let label = format!(
+            "Group \"{}\": {} robux{}{}",
+            self.name
+                .as_ref()
+                .map(|s| &s[..])
+                .unwrap_or("(unknown group name)"),
+            self.robux,
+            self.robux_delta()
+                .map(|delta| format!(" ({:+})", delta))
+                .unwrap_or_default(),
+            status
+        );
+        let expand_button = widget::Button::new(
+            &mut self.expand_state,
+            widget::Text::new(if self.expanded { "-" } else { "+" }),
         )
-        .style(GroupButtonStyle(self.visited))
-        .on_press(Msg::OpenGroup(self.id))
-        .into()
+        .style(ProxyButtonStyle)
+        .on_press(Msg::ToggleGroupDetails(self.id));
+        let open_button = widget::Button::new(&mut self.state, widget::Text::new(label))
+            .style(GroupButtonStyle(self.visited))
+            .width(Length::Fill)
+            .on_press(Msg::OpenGroup(self.id));
+        let mut recheck_button =
+            widget::Button::new(&mut self.recheck_state, widget::Text::new("Recheck"))
+                .style(ProxyButtonStyle);
+        if can_recheck && !self.rechecking {
+            recheck_button = recheck_button.on_press(Msg::RecheckGroup(self.id));
+        }
+        let row = widget::Row::new()
+            .push(expand_button)
+            .push(open_button)
+            .push(recheck_button)
+            .spacing(4)
+            .align_items(iced::Align::Center);
+        if !self.expanded {
+            return row.into();
+        }
+        let mut lines = match &self.details {
+            Some(details) => group_details(details),
+            None => vec![format!(
+                "Group {}: no details were stored for this group",
+                self.id
+            )],
+        };
+        if let Some(err) = &self.recheck_error {
+            lines.push(format!("Checking again failed: {}", err));
+        }
+        if !self.history.is_empty() {
+            lines.push("History:".to_string());
+        }
+        // The most recent checks, newest first
+        for snapshot in self.history.iter().rev().take(10) {
+            lines.push(format!(
+                "  {}: {} robux, {}{}",
+                format_unix_time(snapshot.checked_at),
+                snapshot.robux,
+                if snapshot.owned { "owned" } else { "no owner" },
+                snapshot
+                    .disqualified
+                    .as_ref()
+                    .map(|reason| format!(", no longer qualified: {}", reason))
+                    .unwrap_or_default()
+            ));
+        }
+        widget::Column::new()
+            .push(row)
+            .push(
+                widget::Column::new()
+                    .push(widget::Text::new(lines.join("\n")).size(16))
+                    .padding(4),
+            )
+            .into()
     }
 }
 
+/// What was known of a group when this run found it, like the snapshot the
+/// results database keeps.
+fn found_snapshot(info: &GroupInfoResponse, robux: u32) -> GroupSnapshot {
+    GroupSnapshot {
+        checked_at: unix_time(),
+        robux,
+        owned: info.owner.is_some(),
+        disqualified: None,
+    }
+}
+
+/// The stored info of a found group, shown below it when it is expanded.
+fn group_details(details: &GroupInfoResponse) -> Vec<String> {
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    let mut lines = vec![
+        format!(
+            "Id: {}, members: {}",
+            details.id,
+            details
+                .member_count
+                .map_or_else(|| "unknown".to_string(), |n| n.to_string())
+        ),
+        format!(
+            "Premium only: {}, public entry: {}, locked: {}",
+            yes_no(details.is_builders_club_only),
+            yes_no(details.public_entry_allowed),
+            yes_no(details.locked())
+        ),
+        match &details.owner {
+            Some(owner) => format!("Owner: {} ({})", owner.username, owner.user_id),
+            None => "Owner: none".to_string(),
+        },
+    ];
+    match details.description.as_deref() {
+        Some(description) if !description.trim().is_empty() => {
+            lines.push(format!("Description: {}", description.trim()))
+        }
+        _ => lines.push("Description: none".to_string()),
+    }
+    if let Some(shout) = details.shout.as_ref().filter(|s| !s.body.is_empty()) {
+        let poster = shout
+            .poster
+            .as_ref()
+            .map_or("unknown user", |p| &p.username[..]);
+        let updated = shout.updated.as_deref().or(shout.created.as_deref());
+        lines.push(match updated {
+            Some(updated) => format!("Shout by {} at {}: {}", poster, updated, shout.body),
+            None => format!("Shout by {}: {}", poster, shout.body),
+        });
+    }
+    lines
+}
+
+/// A label followed by radios for showing all groups, or only those with or
+/// without a property.
+fn filter_radios<'a>(
+    label: &str,
+    selected: GroupFilter,
+    msg: fn(GroupFilter) -> Msg,
+) -> Element<'a, Msg> {
+    [
+        (GroupFilter::All, "All"),
+        (GroupFilter::With, "Yes"),
+        (GroupFilter::Without, "No"),
+    ]
+    .iter()
+    .fold(
+        widget::Row::new()
+            .push(widget::Text::new(label))
+            .spacing(8)
+            .align_items(iced::Align::Center),
+        |row, &(value, title)| row.push(widget::Radio::new(value, title, Some(selected), msg)),
+    )
+    .into()
+}
+
 fn header(label: impl Into<String>) -> widget::Text {
     widget::Text::new(label).size(28)
 }
 
+/// The selected proxy's last error, above a table of every proxy's health.
+fn proxy_details<'a>(
+    proxies: &[String],
+    proxy_stats: &BTreeMap<usize, ProxyStats>,
+    selected: usize,
+    state: Option<Connectedness>,
+    (sort_column, descending): (StatsColumn, bool),
+    header_states: &'a mut [widget::button::State; 7],
+    scroll_state: &'a mut widget::scrollable::State,
+) -> Element<'a, Msg> {
+    let no_stats = ProxyStats::default();
+    let selected_stats = proxy_stats.get(&selected).unwrap_or(&no_stats);
+    let title = header(format!(
+        "Proxy {}: {}",
+        selected,
+        proxies.get(selected).map(|p| &p[..]).unwrap_or("")
+    ));
+    let state = widget::Text::new(match state {
+        Some(state) => format!("State: {:?}", state),
+        None => "State: not started".to_string(),
+    });
+    let last_error = widget::Text::new(format!(
+        "Last error: {}",
+        selected_stats.last_error.as_deref().unwrap_or("none")
+    ));
+    let mut header_row = widget::Row::new().spacing(8);
+    for (&column, state) in StatsColumn::ALL.iter().zip(header_states.iter_mut()) {
+        let arrow = match (column == sort_column, descending) {
+            (false, _) => "",
+            (true, false) => " ^",
+            (true, true) => " v",
+        };
+        header_row = header_row.push(
+            widget::Button::new(
+                state,
+                widget::Text::new(format!("{}{}", column.title(), arrow)),
+            )
+            .style(ProxyButtonStyle)
+            .padding(0)
+            .width(Length::FillPortion(1))
+            .on_press(Msg::SortProxyStats(column)),
+        );
+    }
+    let mut rows: Vec<_> = (0..proxies.len())
+        .map(|i| (i, proxy_stats.get(&i).unwrap_or(&no_stats)))
+        .collect();
+    rows.sort_by_key(|&(i, stats)| sort_column.key(i, stats));
+    if descending {
+        rows.reverse();
+    }
+    let mut table = widget::Scrollable::new(scroll_state).height(Length::Fill);
+    for (i, stats) in rows {
+        let color = if i == selected {
+            Color::from_rgb8(0, 39, 142)
+        } else {
+            Color::BLACK
+        };
+        let row = StatsColumn::ALL
+            .iter()
+            .fold(widget::Row::new().spacing(8), |row, column| {
+                row.push(
+                    widget::Text::new(column.cell(i, stats))
+                        .color(color)
+                        .width(Length::FillPortion(1)),
+                )
+            });
+        table = table.push(row);
+    }
+    widget::Column::new()
+        .push(title)
+        .push(state)
+        .push(last_error)
+        .push(header_row)
+        .push(
+            widget::Container::new(table)
+                .padding(4)
+                .height(Length::Fill)
+                .width(Length::Fill)
+                .style(ListStyle),
+        )
+        .padding(4)
+        .spacing(4)
+        .height(Length::FillPortion(2))
+        .into()
+}
+
 struct GroupButtonStyle(bool);
 
 impl widget::button::StyleSheet for GroupButtonStyle {
@@ -126,6 +586,26 @@ impl widget::button::StyleSheet for GroupButtonStyle {
     }
 }
 
+fn level_color(level: Level) -> Color {
+    match level {
+        Level::Error => Color::from_rgb8(206, 10, 10),
+        Level::Warn => Color::from_rgb8(176, 120, 0),
+        Level::Info => Color::BLACK,
+        Level::Debug | Level::Trace => Color::from_rgb8(110, 110, 110),
+    }
+}
+
+struct ProxyButtonStyle;
+
+impl widget::button::StyleSheet for ProxyButtonStyle {
+    fn active(&self) -> widget::button::Style {
+        widget::button::Style {
+            background: None,
+            ..Default::default()
+        }
+    }
+}
+
 struct ListStyle;
 
 impl widget::container::StyleSheet for ListStyle {
@@ -138,59 +618,295 @@ impl widget::container::StyleSheet for ListStyle {
 }
 
 pub struct GroupScraper {
-    proxies_list: Option<Result<Vec<String>, std::io::ErrorKind>>,
+    proxies_list: Option<Result<ProxyList, LoadError>>,
+    /// Open instead of the proxy list while the list is being edited.
+    proxy_editor: Option<ProxyEditor>,
     groups: Vec<GroupInfo>,
     running: bool,
-    running_sender: tokio::sync::watch::Sender<bool>,
-    running_receiver: tokio::sync::watch::Receiver<bool>,
+    scanner: Scanner,
+    scan: Option<Scan>,
     proxies_connected: BTreeMap<usize, Connectedness>,
     start_time: Instant,
     premium_groups: bool,
-    premium_groups_sender: tokio::sync::watch::Sender<bool>,
-    premium_groups_receiver: tokio::sync::watch::Receiver<bool>,
-    minimum_robux: Option<u16>,
-    minimum_robux_sender: tokio::sync::watch::Sender<u16>,
-    minimum_robux_receiver: tokio::sync::watch::Receiver<u16>,
-    groups_checked: u32,
+    /// Text of the robux boxes. The scanner keeps the last range that parsed.
+    minimum_robux: String,
+    maximum_robux: String,
+    /// Why the robux boxes do not make a range.
+    robux_range_error: Option<String>,
+    /// Text of the request rate box, kept as typed so decimals can be entered.
+    requests_per_second: String,
+    /// Why the request rate box does not parse.
+    requests_per_second_error: Option<String>,
+    /// Text of the rule box. The scanner keeps the last rule that parsed.
+    rule: String,
+    /// Why the rule box does not parse.
+    rule_error: Option<String>,
+    /// When the current scan was started, for measuring its request rate.
+    scan_start_time: Instant,
+    groups_checked: u64,
+    /// The last unfinished session, which can be resumed.
+    checkpoint: Option<Checkpoint>,
+    request_errors: BTreeMap<ApiErrorKind, u32>,
+    /// Why the api key could not be loaded, shown until a valid key is saved.
+    api_key_error: Option<String>,
+    api_key_input: String,
+    /// Most recent log records, oldest first.
+    log_entries: VecDeque<Entry>,
+    show_logs: bool,
+    /// Least severe level shown in the log pane.
+    log_level: Level,
+    proxy_stats: BTreeMap<usize, ProxyStats>,
+    /// Proxy whose details are shown.
+    selected_proxy: Option<usize>,
+    /// Column the detail table is sorted by, and whether it is descending.
+    stats_sort: (StatsColumn, bool),
+    /// Column the found groups are sorted by, and whether it is descending.
+    groups_sort: (GroupColumn, bool),
+    group_search: String,
+    visited_filter: GroupFilter,
+    premium_filter: GroupFilter,
     // States
     proxies_scroll_state: widget::scrollable::State,
     new_proxies_button_state: widget::button::State,
+    edit_proxies_button_state: widget::button::State,
     groups_list_state: widget::scrollable::State,
     start_button_state: widget::button::State,
+    resume_button_state: widget::button::State,
+    recheck_all_button_state: widget::button::State,
     minimum_robux_state: widget::text_input::State,
+    maximum_robux_state: widget::text_input::State,
+    requests_per_second_state: widget::text_input::State,
+    rule_state: widget::text_input::State,
+    api_key_input_state: widget::text_input::State,
+    save_api_key_state: widget::button::State,
+    logs_button_state: widget::button::State,
+    logs_scroll_state: widget::scrollable::State,
+    proxy_button_states: Vec<widget::button::State>,
+    stats_header_states: [widget::button::State; 7],
+    stats_scroll_state: widget::scrollable::State,
+    group_header_states: [widget::button::State; 4],
+    group_search_state: widget::text_input::State,
+}
+
+impl GroupScraper {
+    fn load_proxies(&self) -> Command<Msg> {
+        let config = self.scanner.config();
+        Command::perform(
+            get_proxies_list(
+                config.proxies_file.clone(),
+                config.proxy_credentials_file.clone(),
+            ),
+            Msg::ProxyListLoaded,
+        )
+    }
+
+    /// Passes the robux boxes to the scanner if they make a valid range.
+    fn update_robux_range(&mut self) {
+        let minimum = match self.minimum_robux.trim() {
+            "" => Err("enter a minimum, 0 for none".to_string()),
+            s => s
+                .parse::<u32>()
+                .map_err(|e| format!("invalid minimum: {}", e)),
+        };
+        let maximum = match self.maximum_robux.trim() {
+            "" => Ok(None),
+            s => s
+                .parse::<u32>()
+                .map(Some)
+                .map_err(|e| format!("invalid maximum: {}", e)),
+        };
+        let range = minimum
+            .and_then(|minimum| RobuxRange::new(minimum, maximum?).map_err(|e| e.to_string()));
+        match range {
+            Ok(range) => {
+                self.scanner.set_robux_range(range);
+                self.robux_range_error = None;
+            }
+            Err(err) => self.robux_range_error = Some(err),
+        }
+    }
+
+    /// Names of the scan's connections: the proxies, or the direct workers.
+    fn connection_labels(&self) -> Option<Vec<String>> {
+        match (self.scanner.direct_workers(), &self.proxies_list) {
+            (0, Some(Ok(list))) => Some(
+                list.usable()
+                    .map(|entry| entry.name().to_string())
+                    .collect(),
+            ),
+            (0, _) => None,
+            (workers, _) => Some(
+                (0..workers)
+                    .map(|i| format!("Direct connection {}", i))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn sort_groups(&mut self) {
+        let (column, descending) = self.groups_sort;
+        self.groups.sort_by(|a, b| column.compare(a, b));
+        if descending {
+            self.groups.reverse();
+        }
+    }
+
+    /// Checks the groups again in the background, marking them until
+    /// [`Msg::Rechecked`] arrives.
+    fn recheck(&mut self, ids: Vec<GroupId>) -> Command<Msg> {
+        if self.running || ids.is_empty() {
+            return Command::none();
+        }
+        match self.scanner.recheck(ids.clone()) {
+            Ok(recheck) => {
+                for gi in self.groups.iter_mut().filter(|gi| ids.contains(&gi.id)) {
+                    gi.rechecking = true;
+                }
+                Command::perform(recheck, Msg::Rechecked)
+            }
+            Err(err @ StartError::NoApiKey) => {
+                self.api_key_error = Some(err.to_string());
+                Command::none()
+            }
+            Err(err) => {
+                log::error!("event=recheck_failed error=\"{}\"", err);
+                Command::none()
+            }
+        }
+    }
+
+    /// Stops the scan and saves its progress, then loads the checkpoint it
+    /// can be resumed from.
+    fn end_scan(&mut self) -> Command<Msg> {
+        self.running = false;
+        self.scanner.stop();
+        match (self.scan.take(), self.scanner.results()) {
+            (Some(scan), Some(results)) => {
+                let results = results.clone();
+                Command::perform(
+                    async move {
+                        scan.checkpoint().await;
+                        results.last_checkpoint().await.map_err(|e| e.to_string())
+                    },
+                    Msg::CheckpointLoaded,
+                )
+            }
+            _ => Command::none(),
+        }
+    }
+
+    fn begin_scan(&mut self, scan: Result<Scan, StartError>) {
+        match scan {
+            Ok(scan) => {
+                self.running = true;
+                self.scan = Some(scan);
+                self.scan_start_time = Instant::now();
+            }
+            Err(err @ StartError::NoApiKey) => self.api_key_error = Some(err.to_string()),
+            Err(err) => log::error!("event=start_failed error=\"{}\"", err),
+        }
+    }
 }
 
 impl Application for GroupScraper {
     type Executor = iced::executor::Default;
     type Message = Msg;
-    type Flags = ();
-    fn new((): Self::Flags) -> (Self, Command<Self::Message>) {
-        let (running_send, running_recv) = tokio::sync::watch::channel(false);
-        let (premium_send, premium_recv) = tokio::sync::watch::channel(false);
-        let (minimum_robux_send, minimum_robux_recv) = tokio::sync::watch::channel(1);
+    type Flags = Config;
+    fn new(config: Self::Flags) -> (Self, Command<Self::Message>) {
+        let premium_groups = config.premium_groups;
+        let robux_range = config.robux_range();
+        let requests_per_second = config.requests_per_second;
+        let rule = config.group_rule.clone();
+        let mut scanner = Scanner::new(config, Vec::new());
+        match Results::open(&scanner.config().results_db) {
+            Ok(results) => scanner.set_results(results),
+            Err(err) => log::error!(
+                "event=results_error path={} error=\"{}\"",
+                scanner.config().results_db.display(),
+                err
+            ),
+        }
+        let direct_workers = scanner.direct_workers();
+        let mut api_key_error = None;
+        match ApiKey::load(&scanner.config().api_key_file) {
+            Ok(api_key) => scanner.set_api_key(api_key),
+            Err(err) => api_key_error = Some(err.to_string()),
+        }
         let scraper = Self {
             proxies_list: None,
+            proxy_editor: None,
             groups: Vec::new(),
             running: false,
-            running_receiver: running_recv,
-            running_sender: running_send,
+            scanner,
+            scan: None,
             proxies_connected: BTreeMap::new(),
             start_time: Instant::now(),
-            premium_groups: false,
-            premium_groups_receiver: premium_recv,
-            premium_groups_sender: premium_send,
-            minimum_robux: None,
-            minimum_robux_sender: minimum_robux_send,
-            minimum_robux_receiver: minimum_robux_recv,
+            premium_groups,
+            minimum_robux: robux_range.minimum.to_string(),
+            maximum_robux: robux_range
+                .maximum
+                .map(|max| max.to_string())
+                .unwrap_or_default(),
+            robux_range_error: None,
+            requests_per_second_error: None,
+            requests_per_second: if requests_per_second > 0. {
+                requests_per_second.to_string()
+            } else {
+                String::new()
+            },
+            rule,
+            rule_error: None,
+            scan_start_time: Instant::now(),
             groups_checked: 0,
+            checkpoint: None,
+            request_errors: BTreeMap::new(),
+            api_key_error,
+            api_key_input: String::new(),
+            log_entries: logging::history().into(),
+            show_logs: false,
+            log_level: Level::Info,
+            proxy_stats: BTreeMap::new(),
+            selected_proxy: None,
+            stats_sort: (StatsColumn::Proxy, false),
+            groups_sort: (GroupColumn::Robux, true),
+            group_search: String::new(),
+            visited_filter: GroupFilter::All,
+            premium_filter: GroupFilter::All,
             proxies_scroll_state: Default::default(),
             new_proxies_button_state: Default::default(),
+            edit_proxies_button_state: Default::default(),
             groups_list_state: Default::default(),
             start_button_state: Default::default(),
+            resume_button_state: Default::default(),
+            recheck_all_button_state: Default::default(),
             minimum_robux_state: Default::default(),
+            maximum_robux_state: Default::default(),
+            requests_per_second_state: Default::default(),
+            rule_state: Default::default(),
+            api_key_input_state: Default::default(),
+            save_api_key_state: Default::default(),
+            logs_button_state: Default::default(),
+            logs_scroll_state: Default::default(),
+            proxy_button_states: (0..direct_workers).map(|_| Default::default()).collect(),
+            stats_header_states: Default::default(),
+            stats_scroll_state: Default::default(),
+            group_header_states: Default::default(),
+            group_search_state: Default::default(),
         };
-        let command = Command::perform(get_proxies_list(), Msg::ProxyListLoaded);
-        (scraper, command)
+        let mut commands = vec![scraper.load_proxies()];
+        if let Some(results) = scraper.scanner.results() {
+            let results = results.clone();
+            commands.push(Command::perform(
+                async move { results.groups().await.map_err(|e| e.to_string()) },
+                Msg::GroupsLoaded,
+            ));
+            let results = results.clone();
+            commands.push(Command::perform(
+                async move { results.last_checkpoint().await.map_err(|e| e.to_string()) },
+                Msg::CheckpointLoaded,
+            ));
+        }
+        (scraper, Command::batch(commands))
     }
     fn title(&self) -> String {
         "Group Scraper".to_string()
@@ -198,39 +914,106 @@ impl Application for GroupScraper {
     fn update(&mut self, message: Self::Message) -> Command<Self::Message> {
         match message {
             Msg::ProxyListLoaded(res) => {
+                if let Ok(list) = &res {
+                    let targets = list.targets();
+                    if self.scanner.direct_workers() == 0 {
+                        self.proxy_button_states =
+                            targets.iter().map(|_| Default::default()).collect();
+                        self.proxy_stats.clear();
+                        self.selected_proxy = None;
+                    }
+                    self.scanner.set_proxy_list(targets);
+                }
                 self.proxies_list = Some(res);
                 Command::none()
             }
-            Msg::GenerateProxies => Command::perform(generate_proxies_list(), |proxies| {
-                Msg::ProxyListLoaded(proxies.map_err(|_| std::io::ErrorKind::Other))
-            }),
-            Msg::GroupFound {
-                group: (name, id),
-                robux,
-            } => {
-                if !self.groups.iter().any(|gi| gi.id == id) {
-                    self.groups.push(GroupInfo {
-                        name,
-                        id,
-                        robux,
-                        state: Default::default(),
-                        visited: false,
-                    });
-                    self.groups.sort_by_key(|gi| gi.robux);
-                    self.groups.reverse();
+            Msg::ToggleProxyEditor => {
+                if self.proxy_editor.is_some() {
+                    self.proxy_editor = None;
+                } else if !self.running {
+                    let list = match &self.proxies_list {
+                        Some(Ok(list)) => list.clone(),
+                        _ => ProxyList::default(),
+                    };
+                    self.proxy_editor = Some(ProxyEditor::new(self.scanner.config(), list));
                 }
                 Command::none()
             }
+            Msg::ProxyEditor(msg) => {
+                let saved = matches!(msg, EditorMsg::Saved(Ok(_)));
+                let command = match &mut self.proxy_editor {
+                    Some(editor) => editor.update(msg),
+                    None => Command::none(),
+                };
+                if saved {
+                    // Read back so invalid entries are reported with their lines
+                    Command::batch(vec![command, self.load_proxies()])
+                } else {
+                    command
+                }
+            }
+            Msg::GenerateProxies => Command::perform(
+                generate_proxies_list(self.scanner.config().proxies_file.clone()),
+                |proxies| {
+                    Msg::ProxyListLoaded(
+                        proxies.map_err(|_| LoadError::Io(std::io::ErrorKind::Other)),
+                    )
+                },
+            ),
+            Msg::GroupFound { info, robux } => {
+                match self.groups.iter_mut().find(|gi| gi.id == info.id) {
+                    // Found again: keep the entry but show the fresh details.
+                    Some(gi) => {
+                        gi.found = true;
+                        gi.name = Some(info.name.clone());
+                        gi.robux = robux;
+                        gi.premium = info.is_builders_club_only;
+                        gi.history.push(found_snapshot(&info, robux));
+                        gi.details = Some(info);
+                    }
+                    None => self.groups.push(GroupInfo::found(info, robux)),
+                }
+                self.sort_groups();
+                Command::none()
+            }
             Msg::ToggleRunning => {
-                self.running = !self.running;
+                self.proxies_connected.clear();
                 if !self.running {
-                    self.running_sender.broadcast(self.running).unwrap();
+                    self.proxy_stats.clear();
+                    let scan = self.scanner.start();
+                    self.begin_scan(scan);
+                    return Command::none();
                 }
-                let (running_send, running_recv) = tokio::sync::watch::channel(false);
-                self.running_sender = running_send;
-                self.running_receiver = running_recv;
-                self.running_sender.broadcast(self.running).unwrap();
-                self.proxies_connected.clear();
+                self.end_scan()
+            }
+            Msg::ResumeSession => {
+                if let Some(checkpoint) = self.checkpoint.clone() {
+                    self.proxies_connected.clear();
+                    self.proxy_stats.clear();
+                    let groups_checked = checkpoint.progress.groups_checked;
+                    let scan = self.scanner.resume(checkpoint);
+                    if scan.is_ok() {
+                        self.checkpoint = None;
+                        self.groups_checked = groups_checked;
+                    }
+                    self.begin_scan(scan);
+                }
+                Command::none()
+            }
+            Msg::ScanEnded => {
+                if self.running {
+                    self.proxies_connected.clear();
+                    self.end_scan()
+                } else {
+                    Command::none()
+                }
+            }
+            Msg::CheckpointLoaded(Ok(checkpoint)) => {
+                self.checkpoint = checkpoint;
+                Command::none()
+            }
+            Msg::CheckpointLoaded(Err(err)) => {
+                log::error!("event=checkpoint_error error=\"{}\"", err);
                 Command::none()
             }
             Msg::ProxyConnected(index, connectedness) => {
@@ -242,22 +1025,128 @@ impl Application for GroupScraper {
             }
             Msg::SetPremiumGroups(b) => {
                 self.premium_groups = b;
-                self.premium_groups_sender.broadcast(b).unwrap();
+                self.scanner.set_premium_groups(b);
                 Command::none()
             }
             Msg::UpdateMinimumRobux(s) => {
-                let new_min: Result<u16, _> = s.parse();
-                self.minimum_robux = new_min.ok();
-                self.minimum_robux_sender
-                    .broadcast(self.minimum_robux.unwrap_or(1))
-                    .unwrap();
+                self.minimum_robux = s;
+                self.update_robux_range();
+                Command::none()
+            }
+            Msg::UpdateMaximumRobux(s) => {
+                self.maximum_robux = s;
+                self.update_robux_range();
+                Command::none()
+            }
+            Msg::UpdateRequestsPerSecond(s) => {
+                // Only an empty box means no limit, a typo keeps the last rate
+                let rps = match s.trim() {
+                    "" => Ok(0.),
+                    rps => match rps.parse::<f64>() {
+                        Ok(rps) if rps >= 0. && rps.is_finite() => Ok(rps),
+                        Ok(_) => Err("must be a positive number".to_string()),
+                        Err(e) => Err(e.to_string()),
+                    },
+                };
+                match rps {
+                    Ok(rps) => {
+                        self.scanner.set_requests_per_second(rps);
+                        self.requests_per_second_error = None;
+                    }
+                    Err(err) => self.requests_per_second_error = Some(err),
+                }
+                self.requests_per_second = s;
+                Command::none()
+            }
+            Msg::UpdateRule(s) => {
+                match s.parse::<Rule>() {
+                    Ok(rule) => {
+                        self.scanner.set_rule(rule);
+                        self.rule_error = None;
+                    }
+                    Err(err) => self.rule_error = Some(err.to_string()),
+                }
+                self.rule = s;
+                Command::none()
+            }
+            Msg::ToggleGroupDetails(gid) => {
+                if let Some(gi) = self.groups.iter_mut().find(|gi| gi.id == gid) {
+                    gi.expanded = !gi.expanded;
+                }
+                Command::none()
+            }
+            Msg::RecheckGroup(gid) => self.recheck(vec![gid]),
+            Msg::RecheckAll => {
+                let ids = self
+                    .groups
+                    .iter()
+                    .filter(|gi| !gi.rechecking)
+                    .map(|gi| gi.id)
+                    .collect();
+                self.recheck(ids)
+            }
+            Msg::Rechecked(outcomes) => {
+                for (id, outcome) in outcomes {
+                    let gi = match self.groups.iter_mut().find(|gi| gi.id == id) {
+                        Some(gi) => gi,
+                        None => continue,
+                    };
+                    gi.rechecking = false;
+                    match outcome {
+                        Ok(group) => {
+                            gi.name = Some(group.info.name.clone());
+                            gi.robux = group.snapshot.robux;
+                            gi.premium = group.info.is_builders_club_only;
+                            gi.details = Some(group.info);
+                            gi.history.push(group.snapshot);
+                            gi.recheck_error = None;
+                        }
+                        Err(err) => gi.recheck_error = Some(err),
+                    }
+                }
+                self.sort_groups();
                 Command::none()
             }
             Msg::OpenGroup(gid) => {
                 if let Err(err) = opener::open(&format!("https://roblox.com/groups/{}", gid)) {
-                    println!("Could not open link: {}", err);
+                    log::error!("event=open_failed group={} error=\"{}\"", gid, err);
                 } else if let Some(gi) = self.groups.iter_mut().find(|gi| gi.id == gid) {
                     gi.visited = true;
+                    if let Some(results) = self.scanner.results() {
+                        let results = results.clone();
+                        return Command::perform(
+                            async move {
+                                results
+                                    .set_visited(gid, true)
+                                    .await
+                                    .map_err(|e| e.to_string())
+                            },
+                            Msg::VisitedSaved,
+                        );
+                    }
+                }
+                Command::none()
+            }
+            Msg::GroupsLoaded(Ok(stored)) => {
+                for group in stored {
+                    if !self.groups.iter().any(|gi| gi.id == group.id) {
+                        self.groups.push(GroupInfo::stored(group));
+                    }
+                }
+                self.sort_groups();
+                Command::none()
+            }
+            Msg::GroupsLoaded(Err(err)) => {
+                log::error!(
+                    "event=results_error path={} error=\"{}\"",
+                    self.scanner.config().results_db.display(),
+                    err
+                );
+                Command::none()
+            }
+            Msg::VisitedSaved(res) => {
+                if let Err(err) = res {
+                    log::error!("event=results_error error=\"{}\"", err);
                 }
                 Command::none()
             }
@@ -265,55 +1154,206 @@ impl Application for GroupScraper {
                 self.groups_checked += 1;
                 Command::none()
             }
+            Msg::RequestFailed(_, kind) => {
+                *self.request_errors.entry(kind).or_insert(0) += 1;
+                Command::none()
+            }
+            Msg::ApiKeyInputChanged(s) => {
+                self.api_key_input = s;
+                Command::none()
+            }
+            Msg::SaveApiKey => match ApiKey::parse(&self.api_key_input) {
+                Ok(api_key) => {
+                    let path = self.scanner.config().api_key_file.clone();
+                    Command::perform(
+                        async move {
+                            api_key
+                                .save(&path)
+                                .await
+                                .map(|()| api_key)
+                                .map_err(|e| e.to_string())
+                        },
+                        Msg::ApiKeySaved,
+                    )
+                }
+                Err(err) => {
+                    self.api_key_error = Some(err.to_string());
+                    Command::none()
+                }
+            },
+            Msg::ApiKeySaved(Ok(api_key)) => {
+                self.scanner.set_api_key(api_key);
+                self.api_key_error = None;
+                self.api_key_input.clear();
+                Command::none()
+            }
+            Msg::ApiKeySaved(Err(err)) => {
+                self.api_key_error = Some(format!("the api key could not be saved: {}", err));
+                Command::none()
+            }
+            Msg::LogEntry(entry) => {
+                if self.log_entries.len() == logging::HISTORY_LEN {
+                    self.log_entries.pop_front();
+                }
+                self.log_entries.push_back(entry);
+                Command::none()
+            }
+            Msg::ToggleLogs => {
+                self.show_logs = !self.show_logs;
+                Command::none()
+            }
+            Msg::SetLogLevel(level) => {
+                self.log_level = level;
+                Command::none()
+            }
+            Msg::ProxyStats(index, stats) => {
+                self.proxy_stats.insert(index, stats);
+                Command::none()
+            }
+            Msg::SelectProxy(index) => {
+                if self.selected_proxy == Some(index) {
+                    self.selected_proxy = None;
+                } else {
+                    self.selected_proxy = Some(index);
+                }
+                Command::none()
+            }
+            Msg::SetDirect(direct) => {
+                if !self.running {
+                    let workers = if direct {
+                        self.scanner.config().direct_workers.max(1)
+                    } else {
+                        0
+                    };
+                    self.scanner.set_direct_workers(workers);
+                    self.proxy_button_states = match self.connection_labels() {
+                        Some(labels) => labels.iter().map(|_| Default::default()).collect(),
+                        None => Vec::new(),
+                    };
+                    self.proxies_connected.clear();
+                    self.proxy_stats.clear();
+                    self.selected_proxy = None;
+                }
+                Command::none()
+            }
+            Msg::SortProxyStats(column) => {
+                self.stats_sort = match self.stats_sort {
+                    (sorted, descending) if sorted == column => (column, !descending),
+                    _ => (column, true),
+                };
+                Command::none()
+            }
+            Msg::SortGroups(column) => {
+                self.groups_sort = match self.groups_sort {
+                    (sorted, descending) if sorted == column => (column, !descending),
+                    // Text reads best from A, numbers and times from the largest
+                    _ => (column, column != GroupColumn::Name),
+                };
+                self.sort_groups();
+                Command::none()
+            }
+            Msg::SearchGroups(search) => {
+                self.group_search = search;
+                Command::none()
+            }
+            Msg::FilterVisited(filter) => {
+                self.visited_filter = filter;
+                Command::none()
+            }
+            Msg::FilterPremium(filter) => {
+                self.premium_filter = filter;
+                Command::none()
+            }
         }
     }
     fn view(&mut self) -> Element<'_, Self::Message> {
         let proxies_header = header("Proxies");
-        let proxies_widget: Element<_> = match &self.proxies_list {
-            None => widget::Text::new("Loading proxies").into(),
-            Some(Err(std::io::ErrorKind::NotFound)) => widget::Text::new(
-                "Thank you for using my program.\n
+        let labels = self.connection_labels();
+        let editing_proxies = self.proxy_editor.is_some();
+        let proxies_file = self.scanner.config().proxies_file.clone();
+        let proxies_widget: Element<_> = if let Some(editor) = &mut self.proxy_editor {
+            editor.view()
+        } else {
+            match (&labels, &self.proxies_list) {
+                (None, None) | (None, Some(Ok(_))) => widget::Text::new("Loading proxies").into(),
+                (None, Some(Err(LoadError::Io(std::io::ErrorKind::NotFound)))) => widget::Text::new(
+                    "Thank you for using my program.\n
 To scrape groups, you must first create a list of proxies to scrape with.\n
-Click the button below to automatically generate one.\n
-You will also need an api.key file in the same directory as this program.",
-            )
-            .size(16)
-            .vertical_alignment(VerticalAlignment::Center)
-            .height(Length::Fill)
-            .into(),
-            Some(Err(error)) => {
-                widget::Text::new(format!("Loading proxies.json failed: {:?}", error)).into()
-            }
-            Some(Ok(proxies)) => {
-                let mut proxy_list =
-                    widget::Scrollable::new(&mut self.proxies_scroll_state).width(Length::Fill);
-                for (i, p) in proxies.iter().enumerate() {
-                    let text_color = match self.proxies_connected.get(&i) {
-                        Some(Connectedness::Connected) => Color::from_rgb8(32, 219, 82),
-                        Some(Connectedness::RateLimited) => Color::from_rgb8(206, 206, 10),
-                        None | Some(Connectedness::Unconnected) => Color::from_rgb8(206, 10, 10),
-                    };
-                    proxy_list = proxy_list.push(widget::Text::new(p).color(text_color));
+Click the button below to automatically generate one, or connect directly to scan slowly without proxies.\n
+You will also need an api key, which is read from the file set by api_key_file.",
+                )
+                .size(16)
+                .vertical_alignment(VerticalAlignment::Center)
+                .height(Length::Fill)
+                .into(),
+                (None, Some(Err(error))) => widget::Text::new(format!(
+                    "Loading {} failed: {}",
+                    proxies_file.display(),
+                    error
+                ))
+                .into(),
+                (Some(proxies), list) => {
+                    let mut proxy_list =
+                        widget::Scrollable::new(&mut self.proxies_scroll_state).width(Length::Fill);
+                    for ((i, p), state) in proxies
+                        .iter()
+                        .enumerate()
+                        .zip(self.proxy_button_states.iter_mut())
+                    {
+                        let text_color = match self.proxies_connected.get(&i) {
+                            Some(Connectedness::Active) => Color::from_rgb8(32, 219, 82),
+                            Some(Connectedness::CoolingDown) => Color::from_rgb8(206, 206, 10),
+                            Some(Connectedness::Connecting) | Some(Connectedness::Backoff) => {
+                                Color::from_rgb8(230, 130, 20)
+                            }
+                            None | Some(Connectedness::Retired) => Color::from_rgb8(206, 10, 10),
+                        };
+                        let label = if self.selected_proxy == Some(i) {
+                            format!("> {}", p)
+                        } else {
+                            p.clone()
+                        };
+                        proxy_list = proxy_list.push(
+                            widget::Button::new(state, widget::Text::new(label).color(text_color))
+                                .style(ProxyButtonStyle)
+                                .padding(0)
+                                .on_press(Msg::SelectProxy(i)),
+                        );
+                    }
+                    let proxy_list_container = widget::Container::new(proxy_list)
+                        .padding(4)
+                        .style(ListStyle);
+                    let proxies_connected = self
+                        .proxies_connected
+                        .iter()
+                        .filter(|(&_, &v)| v.is_connected())
+                        .count();
+                    let proxy_connections = widget::Text::new(format!(
+                        "{} proxies connected ({}%)",
+                        proxies_connected,
+                        ((proxies_connected as f32 / proxies.len() as f32) * 100.0) as u8
+                    ));
+                    let mut column = widget::Column::new()
+                        .push(proxy_connections)
+                        .push(proxy_list_container)
+                        .padding(4)
+                        .height(iced::Length::Fill);
+                    if let Some(Ok(list)) = list {
+                        for (line, reason) in &list.invalid {
+                            column = column.push(
+                                widget::Text::new(format!(
+                                    "Skipped {} line {}: {}",
+                                    proxies_file.display(),
+                                    line,
+                                    reason
+                                ))
+                                .size(14)
+                                .color(Color::from_rgb8(206, 10, 10)),
+                            );
+                        }
+                    }
+                    column.into()
                 }
-                let proxy_list_container = widget::Container::new(proxy_list)
-                    .padding(4)
-                    .style(ListStyle);
-                let proxies_connected = self
-                    .proxies_connected
-                    .iter()
-                    .filter(|(&_, &v)| v != Connectedness::Unconnected)
-                    .count();
-                let proxy_connections = widget::Text::new(format!(
-                    "{} proxies connected ({}%)",
-                    proxies_connected,
-                    ((proxies_connected as f32 / proxies.len() as f32) * 100.0) as u8
-                ));
-                widget::Column::new()
-                    .push(proxy_connections)
-                    .push(proxy_list_container)
-                    .padding(4)
-                    .height(iced::Length::Fill)
-                    .into()
             }
         };
         let mut new_proxies_button = widget::Button::new(
@@ -324,15 +1364,38 @@ You will also need an api.key file in the same directory as this program.",
             new_proxies_button = new_proxies_button.on_press(Msg::GenerateProxies);
         }
         let new_proxies_button = new_proxies_button;
+        let mut edit_proxies_button = widget::Button::new(
+            &mut self.edit_proxies_button_state,
+            widget::Text::new(if editing_proxies {
+                "Close editor"
+            } else {
+                "Edit proxies"
+            }),
+        );
+        if !self.running {
+            edit_proxies_button = edit_proxies_button.on_press(Msg::ToggleProxyEditor);
+        }
+        let proxy_buttons = widget::Row::new()
+            .push(new_proxies_button)
+            .push(edit_proxies_button)
+            .spacing(8);
+        let direct_checkbox = widget::Checkbox::new(
+            self.scanner.direct_workers() > 0,
+            "Connect directly, without proxies",
+            Msg::SetDirect,
+        );
         let proxies_column = widget::Column::new()
             .push(proxies_header)
             .push(proxies_widget)
-            .push(new_proxies_button)
+            .push(proxy_buttons)
+            .push(direct_checkbox)
+            .spacing(4)
             .width(Length::FillPortion(4));
 
         let robux_found: u32 = self
             .groups
             .iter()
+            .filter(|gi| gi.found)
             .map(|GroupInfo { robux, .. }| robux)
             .sum();
         let time_elapsed = self.start_time.elapsed().as_secs_f32();
@@ -346,29 +1409,161 @@ You will also need an api.key file in the same directory as this program.",
         };
         let best_metric =
             (((robux_per_second / closest_premium.robux_per_second()) - 1.) * 100.) as i32;
+        let failed_requests = self.request_errors.values().sum::<u32>();
+        let failed_requests = if failed_requests == 0 {
+            String::new()
+        } else {
+            let counts = self
+                .request_errors
+                .iter()
+                .map(|(kind, count)| format!("{} {}", count, kind))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("\n{} failed requests ({})", failed_requests, counts)
+        };
+        let coverage = match self.scan.as_ref().and_then(Scan::coverage) {
+            Some(coverage) => format!(" ({:.2}% of range covered)", coverage),
+            None => String::new(),
+        };
+        let request_rate = match &self.scan {
+            Some(scan) => {
+                let elapsed = self.scan_start_time.elapsed().as_secs_f64().max(1.);
+                let limit = match scan.rate_limit() {
+                    rps if rps > 0. => format!(" (limit {})", rps),
+                    _ => String::new(),
+                };
+                format!(
+                    "\n{:.1} requests per second{}",
+                    scan.requests_sent() as f64 / elapsed,
+                    limit
+                )
+            }
+            None => String::new(),
+        };
         let robux_count = widget::Text::new(format!(
-            "Total robux found: {}\n{} groups checked\n{}% better than {} premium",
-            robux_found, self.groups_checked, best_metric, closest_premium.price,
+            "Total robux found: {}\n{} groups checked{}{}{}\n{}% better than {} premium",
+            robux_found,
+            self.groups_checked,
+            coverage,
+            request_rate,
+            failed_requests,
+            best_metric,
+            closest_premium.price,
         ))
         .horizontal_alignment(HorizontalAlignment::Center);
+        let query = GroupQuery {
+            search: self.group_search.trim().to_lowercase(),
+            visited: self.visited_filter,
+            premium: self.premium_filter,
+        };
+        let (sort_column, descending) = self.groups_sort;
+        let mut sort_row = widget::Row::new().spacing(8);
+        for (&column, state) in GroupColumn::ALL
+            .iter()
+            .zip(self.group_header_states.iter_mut())
+        {
+            let arrow = match (column == sort_column, descending) {
+                (false, _) => "",
+                (true, false) => " ^",
+                (true, true) => " v",
+            };
+            sort_row = sort_row.push(
+                widget::Button::new(
+                    state,
+                    widget::Text::new(format!("{}{}", column.title(), arrow)),
+                )
+                .style(ProxyButtonStyle)
+                .padding(0)
+                .on_press(Msg::SortGroups(column)),
+            );
+        }
         let mut groups_list =
             widget::Scrollable::new(&mut self.groups_list_state).height(Length::Fill);
         let groups_found = self.groups.len();
-        for gi in self.groups.iter_mut() {
-            groups_list = groups_list.push(gi.view());
+        let mut groups_shown = 0;
+        let can_recheck = !self.running && self.scanner.can_start();
+        for gi in self.groups.iter_mut().filter(|gi| query.matches(gi)) {
+            groups_shown += 1;
+            groups_list = groups_list.push(gi.view(can_recheck));
         }
-        let start_button = widget::Button::new(
+        let mut start_button = widget::Button::new(
             &mut self.start_button_state,
             widget::Text::new(if self.running {
                 "Stop"
             } else {
                 "Start scraping"
             }),
+        );
+        if self.running || self.scanner.can_start() {
+            start_button = start_button.on_press(Msg::ToggleRunning);
+        }
+        let start_button = start_button;
+        let mut start_row = widget::Row::new().push(start_button).spacing(16);
+        if let (false, Some(checkpoint)) = (self.running, &self.checkpoint) {
+            let mut resume_button = widget::Button::new(
+                &mut self.resume_button_state,
+                widget::Text::new(format!(
+                    "Resume last session ({} groups checked)",
+                    checkpoint.progress.groups_checked
+                )),
+            );
+            if self.scanner.can_start() {
+                resume_button = resume_button.on_press(Msg::ResumeSession);
+            }
+            start_row = start_row.push(resume_button);
+        }
+        let logs_button = widget::Button::new(
+            &mut self.logs_button_state,
+            widget::Text::new(if self.show_logs {
+                "Hide logs"
+            } else {
+                "Show logs"
+            }),
         )
-        .on_press(Msg::ToggleRunning);
-        let groups_header = header(format!("Groups found ({})", groups_found))
-            .width(Length::Fill)
-            .horizontal_alignment(HorizontalAlignment::Left);
+        .on_press(Msg::ToggleLogs);
+        let start_row = start_row.push(logs_button);
+        let mut recheck_all_button = widget::Button::new(
+            &mut self.recheck_all_button_state,
+            widget::Text::new("Recheck all"),
+        );
+        if groups_found > 0 && can_recheck {
+            recheck_all_button = recheck_all_button.on_press(Msg::RecheckAll);
+        }
+        let groups_header = widget::Row::new()
+            .push(
+                header(if groups_shown == groups_found {
+                    format!("Groups found ({})", groups_found)
+                } else {
+                    format!("Groups found ({} of {})", groups_shown, groups_found)
+                })
+                .width(Length::Fill)
+                .horizontal_alignment(HorizontalAlignment::Left),
+            )
+            .push(recheck_all_button)
+            .align_items(iced::Align::Center);
+        let search_textbox = widget::TextInput::new(
+            &mut self.group_search_state,
+            "Search by name or id",
+            &self.group_search,
+            Msg::SearchGroups,
+        );
+        let filter_row = widget::Row::new()
+            .push(filter_radios(
+                "Visited:",
+                self.visited_filter,
+                Msg::FilterVisited,
+            ))
+            .push(filter_radios(
+                "Premium:",
+                self.premium_filter,
+                Msg::FilterPremium,
+            ))
+            .spacing(24);
+        let groups_list = widget::Column::new()
+            .push(sort_row)
+            .push(groups_list)
+            .spacing(4)
+            .height(Length::Fill);
         let groups_list_container = widget::Container::new(groups_list)
             .padding(4)
             .height(Length::Fill)
@@ -382,42 +1577,170 @@ You will also need an api.key file in the same directory as this program.",
         let minimum_textbox = widget::TextInput::new(
             &mut self.minimum_robux_state,
             "Minimum robux",
-            &self
-                .minimum_robux
-                .map(|r| r.to_string())
-                .unwrap_or_else(|| "".to_string()),
+            &self.minimum_robux,
             Msg::UpdateMinimumRobux,
         );
+        let maximum_textbox = widget::TextInput::new(
+            &mut self.maximum_robux_state,
+            "Maximum robux (no limit)",
+            &self.maximum_robux,
+            Msg::UpdateMaximumRobux,
+        );
+        let requests_per_second_textbox = widget::TextInput::new(
+            &mut self.requests_per_second_state,
+            "Max requests per second (no limit)",
+            &self.requests_per_second,
+            Msg::UpdateRequestsPerSecond,
+        );
         let config_row = widget::Row::new()
             .push(minimum_textbox)
+            .push(maximum_textbox)
+            .push(requests_per_second_textbox)
             .push(premium_checkbox)
             .spacing(16)
             .align_items(iced::Align::Center);
-        let robux_column = widget::Column::new()
-            .push(robux_count)
-            .push(start_button)
+        let rule_textbox = widget::TextInput::new(
+            &mut self.rule_state,
+            "Rule, e.g. public && !locked && !owned && member_count < 50",
+            &self.rule,
+            Msg::UpdateRule,
+        );
+        let mut rule_column = widget::Column::new().push(rule_textbox).spacing(2);
+        if let Some(err) = &self.robux_range_error {
+            rule_column = rule_column.push(
+                widget::Text::new(format!(
+                    "Invalid robux range, the last valid one is used: {}",
+                    err
+                ))
+                .size(14)
+                .color(Color::from_rgb8(206, 10, 10)),
+            );
+        }
+        if let Some(err) = &self.requests_per_second_error {
+            rule_column = rule_column.push(
+                widget::Text::new(format!(
+                    "Invalid request rate, the last valid one is used: {}",
+                    err
+                ))
+                .size(14)
+                .color(Color::from_rgb8(206, 10, 10)),
+            );
+        }
+        if let Some(err) = &self.rule_error {
+            rule_column = rule_column.push(
+                widget::Text::new(format!("Invalid rule, the last valid one is used: {}", err))
+                    .size(14)
+                    .color(Color::from_rgb8(206, 10, 10)),
+            );
+        }
+        let mut robux_column = widget::Column::new().push(robux_count).push(start_row);
+        if let Some(err) = &self.api_key_error {
+            let api_key_help = widget::Text::new(format!(
+                "Could not load the api key: {}.\nPaste your key below to save it to {}.",
+                err,
+                self.scanner.config().api_key_file.display()
+            ))
+            .color(Color::from_rgb8(206, 10, 10))
+            .horizontal_alignment(HorizontalAlignment::Center);
+            let api_key_textbox = widget::TextInput::new(
+                &mut self.api_key_input_state,
+                "Api key",
+                &self.api_key_input,
+                Msg::ApiKeyInputChanged,
+            )
+            .password()
+            .on_submit(Msg::SaveApiKey);
+            let save_api_key_button =
+                widget::Button::new(&mut self.save_api_key_state, widget::Text::new("Save key"))
+                    .on_press(Msg::SaveApiKey);
+            let api_key_row = widget::Row::new()
+                .push(api_key_textbox)
+                .push(save_api_key_button)
+                .spacing(16)
+                .align_items(iced::Align::Center);
+            robux_column = robux_column.push(api_key_help).push(api_key_row);
+        }
+        let robux_column = robux_column
             .push(groups_header)
+            .push(search_textbox)
+            .push(filter_row)
             .push(groups_list_container)
             .push(config_row)
+            .push(rule_column)
             .spacing(4)
             .width(Length::FillPortion(6))
             .align_items(iced::Align::Center);
-        widget::Row::new()
+        let main_row = widget::Row::new()
             .push(proxies_column)
             .push(robux_column)
             .padding(4)
             .spacing(16)
-            .into()
+            .height(Length::FillPortion(3));
+        let mut content = widget::Column::new().push(main_row);
+        if let (Some(selected), Some(labels)) = (self.selected_proxy, &labels) {
+            content = content.push(proxy_details(
+                labels,
+                &self.proxy_stats,
+                selected,
+                self.proxies_connected.get(&selected).copied(),
+                self.stats_sort,
+                &mut self.stats_header_states,
+                &mut self.stats_scroll_state,
+            ));
+        }
+        if !self.show_logs {
+            return content.into();
+        }
+        let level_row = [Level::Error, Level::Warn, Level::Info, Level::Debug]
+            .iter()
+            .fold(
+                widget::Row::new()
+                    .push(widget::Text::new("Show:"))
+                    .spacing(16),
+                |row, &level| {
+                    row.push(widget::Radio::new(
+                        level,
+                        level.to_string(),
+                        Some(self.log_level),
+                        Msg::SetLogLevel,
+                    ))
+                },
+            );
+        let mut log_list = widget::Scrollable::new(&mut self.logs_scroll_state).width(Length::Fill);
+        for entry in self
+            .log_entries
+            .iter()
+            .filter(|e| e.level <= self.log_level)
+        {
+            log_list = log_list.push(
+                widget::Text::new(entry.to_string())
+                    .size(14)
+                    .color(level_color(entry.level)),
+            );
+        }
+        let log_pane = widget::Column::new()
+            .push(header("Logs"))
+            .push(level_row)
+            .push(
+                widget::Container::new(log_list)
+                    .padding(4)
+                    .height(Length::Fill)
+                    .width(Length::Fill)
+                    .style(ListStyle),
+            )
+            .padding(4)
+            .spacing(4)
+            .height(Length::FillPortion(2));
+        content.push(log_pane).into()
     }
     fn subscription(&self) -> Subscription<Self::Message> {
-        match (self.running, &self.proxies_list) {
-            (true, Some(Ok(list))) => iced::Subscription::from_recipe(crate::scraping::Scraping {
-                proxy_list: list.clone(),
-                running: self.running_receiver.clone(),
-                premium_groups: self.premium_groups_receiver.clone(),
-                minimum_robux: self.minimum_robux_receiver.clone(),
-            }),
-            _ => iced::Subscription::none(),
+        let logs = iced::Subscription::from_recipe(crate::scraping::Logs);
+        match &self.scan {
+            Some(scan) => iced::Subscription::batch(vec![
+                logs,
+                iced::Subscription::from_recipe(crate::scraping::Scraping(scan.clone())),
+            ]),
+            None => logs,
         }
     }
 }